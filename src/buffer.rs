@@ -0,0 +1,493 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::{
+    GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation,
+};
+
+use crate::history::{Edit, History};
+
+/// A file (or a scratch area) open for editing: its text, where it came
+/// from, and its undo history.
+///
+/// Positions are (x, y) pairs, where y is the line index and x is the char
+/// index within that line. Line endings ("\n" or "\r\n") are not part of
+/// the line as seen through `line`, `line_len` or `line_slice`, but are kept
+/// verbatim in the underlying rope, so that saving writes back exactly what
+/// was read, plus any edits.
+///
+/// Every change made through `insert` and `remove` is recorded in the
+/// buffer's undo history.
+#[derive(Debug, Clone)]
+pub struct Buffer {
+    // Shown in the status line, unique among open buffers
+    pub name: String,
+    // Absolute path of the file, if the buffer has one
+    pub path: Option<PathBuf>,
+    // Set by edits that change the text, cleared on saving
+    pub modified: bool,
+    // Cursor position and scroll offset, remembered while not displayed
+    pub cursor: (usize, usize),
+    pub offset: (usize, usize),
+
+    text: Rope,
+    history: History,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer {
+            name: String::from("*scratch*"),
+            path: None,
+            modified: false,
+            cursor: (0, 0),
+            offset: (0, 0),
+            text: Rope::new(),
+            history: History::new(),
+        }
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Buffer> {
+        Ok(Buffer {
+            text: Rope::from_reader(reader)?,
+            ..Buffer::new()
+        })
+    }
+
+    #[cfg(test)]
+    pub fn from_str(s: &str) -> Buffer {
+        Buffer {
+            text: Rope::from_str(s),
+            ..Buffer::new()
+        }
+    }
+
+    /// Read the file at path. A file that does not exist yet gives an empty
+    /// buffer, which will create the file when saved.
+    pub fn open(path: &Path) -> io::Result<Buffer> {
+        let mut buf = match File::open(path) {
+            Ok(file) => Buffer::from_reader(io::BufReader::new(file))?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Buffer::new()
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(bname) = path.file_name() {
+            buf.name = bname.to_string_lossy().into_owned();
+        }
+        buf.set_path(path)?;
+        Ok(buf)
+    }
+
+    /// Associate the buffer with the file at path, made absolute.
+    pub fn set_path(&mut self, path: &Path) -> io::Result<()> {
+        self.path = Some(match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => env::current_dir()?.join(path),
+        });
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.text.write_to(writer)
+    }
+
+    /// Write the buffer to its file, and mark it as not modified.
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "buffer has no file name",
+                ))
+            }
+        };
+        self.write_atomic(path)?;
+        self.modified = false;
+        Ok(())
+    }
+
+    /// Write the buffer to path through a temporary file in the same
+    /// directory, so that a crash or a full disk never leaves a half-written
+    /// file behind. Permissions of an existing file are carried over.
+    fn write_atomic(&self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let tmp = dir.join(format!(".{}.rk{}~", name, std::process::id()));
+        let perms = match fs::metadata(path) {
+            Ok(meta) => Some(meta.permissions()),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        let r = (|| {
+            let file =
+                OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+            let mut w = io::BufWriter::new(file);
+            self.write_to(&mut w)?;
+            let file = w.into_inner().map_err(|err| err.into_error())?;
+            if let Some(perms) = perms {
+                file.set_permissions(perms)?;
+            }
+            file.sync_all()?;
+            fs::rename(&tmp, path)
+        })();
+        if r.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        r
+    }
+
+    /// Number of lines. Never zero: an empty buffer has one empty line, and
+    /// a trailing newline starts a new (empty) last line.
+    pub fn len_lines(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text.len_chars()
+    }
+
+    fn line_raw(&self, y: usize) -> RopeSlice<'_> {
+        let line = self.text.line(y);
+        let mut len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len -= 1;
+            if len > 0 && line.char(len - 1) == '\r' {
+                len -= 1;
+            }
+        }
+        line.slice(..len)
+    }
+
+    /// The char index in line y where the spaces and tabs at its end
+    /// start, or its length if there are none.
+    pub fn trailing_whitespace(&self, y: usize) -> usize {
+        let line = self.line_raw(y);
+        let mut x = line.len_chars();
+        let mut chars = line.chars_at(x);
+        while let Some(' ' | '\t') = chars.prev() {
+            x -= 1;
+        }
+        x
+    }
+
+    /// Length of line y in chars, excluding the line ending.
+    pub fn line_len(&self, y: usize) -> usize {
+        self.line_raw(y).len_chars()
+    }
+
+    /// Line y, excluding the line ending.
+    pub fn line(&self, y: usize) -> String {
+        self.line_raw(y).to_string()
+    }
+
+    /// Chars start..end of line y, clamped to the line length. Only the
+    /// requested part of the line is copied, so this stays cheap on very
+    /// long lines.
+    pub fn line_slice(&self, y: usize, start: usize, end: usize) -> String {
+        let line = self.line_raw(y);
+        let len = line.len_chars();
+        let end = end.min(len);
+        let start = start.min(end);
+        line.slice(start..end).to_string()
+    }
+
+    /// The char index in line y of the grapheme boundary after x.
+    pub fn next_grapheme(&self, x: usize, y: usize) -> usize {
+        grapheme_boundary(self.line_raw(y), x, true)
+    }
+
+    /// The char index in line y of the grapheme boundary before x.
+    pub fn prev_grapheme(&self, x: usize, y: usize) -> usize {
+        grapheme_boundary(self.line_raw(y), x, false)
+    }
+
+    /// The char index in line y of the grapheme boundary at or before x.
+    pub fn grapheme_floor(&self, x: usize, y: usize) -> usize {
+        let line = self.line_raw(y);
+        if x >= line.len_chars() {
+            return line.len_chars();
+        }
+        grapheme_boundary(line, x + 1, false)
+    }
+
+    /// The text between two positions. The order of the positions does not
+    /// matter.
+    pub fn slice(&self, from: (usize, usize), to: (usize, usize)) -> String {
+        let a = self.pos_to_char(from.0, from.1);
+        let b = self.pos_to_char(to.0, to.1);
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        self.text.slice(a..b).to_string()
+    }
+
+    pub fn char_at(&self, idx: usize) -> char {
+        self.text.char(idx)
+    }
+
+    /// The char index just past the end of the next word from idx, as in
+    /// Emacs: anything that isn't a word is skipped first. Words are split
+    /// by the Unicode word boundary rules.
+    pub fn word_end(&self, idx: usize) -> usize {
+        let (mut x, mut y) = self.char_to_pos(idx.min(self.len_chars()));
+        loop {
+            let mut end = x;
+            for part in self.line_slice(y, x, usize::MAX).split_word_bounds() {
+                end += part.chars().count();
+                if is_word(part) {
+                    return self.pos_to_char(end, y);
+                }
+            }
+            if y + 1 >= self.len_lines() {
+                return self.len_chars();
+            }
+            (x, y) = (0, y + 1);
+        }
+    }
+
+    /// The char index of the start of the word before idx.
+    pub fn word_start(&self, idx: usize) -> usize {
+        let (x, mut y) = self.char_to_pos(idx.min(self.len_chars()));
+        let mut before = self.line_slice(y, 0, x);
+        loop {
+            let mut start = before.chars().count();
+            for part in before.split_word_bounds().rev() {
+                start -= part.chars().count();
+                if is_word(part) {
+                    return self.pos_to_char(start, y);
+                }
+            }
+            if y == 0 {
+                return 0;
+            }
+            y -= 1;
+            before = self.line(y);
+        }
+    }
+
+    /// The words of line y as ranges of chars, for vi motions: words split
+    /// by the Unicode word boundary rules, and runs of punctuation between
+    /// them. With big, anything between blanks is a single word, a vi
+    /// WORD.
+    pub fn words(&self, y: usize, big: bool) -> Vec<(usize, usize)> {
+        let mut words: Vec<(usize, usize, bool)> = Vec::new();
+        let mut x = 0;
+        for part in self.line(y).split_word_bounds() {
+            let n = part.chars().count();
+            let word = is_word(part);
+            if !part.chars().all(char::is_whitespace) {
+                match words.last_mut() {
+                    Some((_, end, w))
+                        if *end == x && (big || !word && !*w) =>
+                    {
+                        *end += n
+                    }
+                    _ => words.push((x, x + n, word)),
+                }
+            }
+            x += n;
+        }
+        words.into_iter().map(|(a, b, _)| (a, b)).collect()
+    }
+
+    /// Column of the first non-blank char of line y.
+    pub fn first_non_blank(&self, y: usize) -> usize {
+        let line = self.line(y);
+        line.chars()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(line.chars().count())
+    }
+
+    fn is_blank(&self, y: usize) -> bool {
+        self.line(y).trim().is_empty()
+    }
+
+    /// The blank line after the paragraph at or below line y, or the last
+    /// line if there is none.
+    pub fn paragraph_end(&self, y: usize) -> usize {
+        let last = self.len_lines() - 1;
+        let mut y = y.min(last);
+        while y < last && self.is_blank(y) {
+            y += 1;
+        }
+        while y < last && !self.is_blank(y) {
+            y += 1;
+        }
+        y
+    }
+
+    /// The blank line before the paragraph at or above line y, or the first
+    /// line if there is none.
+    pub fn paragraph_start(&self, y: usize) -> usize {
+        let mut y = y.min(self.len_lines() - 1);
+        while y > 0 && self.is_blank(y) {
+            y -= 1;
+        }
+        while y > 0 && !self.is_blank(y) {
+            y -= 1;
+        }
+        y
+    }
+
+    /// The whole text, including line endings.
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    pub fn byte_to_char(&self, idx: usize) -> usize {
+        self.text.byte_to_char(idx)
+    }
+
+    pub fn char_to_byte(&self, idx: usize) -> usize {
+        self.text.char_to_byte(idx)
+    }
+
+    /// Convert an (x, y) position to a char index into the whole buffer.
+    /// Out of range positions are clamped to the nearest valid one.
+    pub fn pos_to_char(&self, x: usize, y: usize) -> usize {
+        let y = y.min(self.len_lines() - 1);
+        self.text.line_to_char(y) + x.min(self.line_len(y))
+    }
+
+    /// Convert a char index into the whole buffer to an (x, y) position.
+    pub fn char_to_pos(&self, idx: usize) -> (usize, usize) {
+        let idx = idx.min(self.len_chars());
+        let y = self.text.char_to_line(idx);
+        let x = (idx - self.text.line_to_char(y)).min(self.line_len(y));
+        (x, y)
+    }
+
+    /// Insert s at (x, y), and return the position just past the inserted
+    /// text.
+    pub fn insert(&mut self, x: usize, y: usize, s: &str) -> (usize, usize) {
+        let idx = self.pos_to_char(x, y);
+        if s.is_empty() {
+            return self.char_to_pos(idx);
+        }
+        self.history.record(
+            Edit::Insert {
+                at: idx,
+                text: s.into(),
+            },
+            (x, y),
+        );
+        self.text.insert(idx, s);
+        self.modified = true;
+        self.char_to_pos(idx + s.chars().count())
+    }
+
+    /// Remove the text between two positions, and return it. The order of
+    /// the positions does not matter.
+    pub fn remove(
+        &mut self,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> String {
+        let a = self.pos_to_char(from.0, from.1);
+        let b = self.pos_to_char(to.0, to.1);
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        let removed = self.text.slice(a..b).to_string();
+        if a < b {
+            self.history.record(
+                Edit::Remove {
+                    at: a,
+                    text: removed.clone(),
+                },
+                self.char_to_pos(a),
+            );
+            self.modified = true;
+        }
+        self.text.remove(a..b);
+        removed
+    }
+
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { at, text } => self.text.insert(*at, text),
+            Edit::Remove { at, text } => {
+                self.text.remove(*at..*at + text.chars().count())
+            }
+        }
+    }
+
+    /// Start an undo step, unless one is still open. Edits made until the
+    /// next call to `seal` are undone together.
+    pub fn begin_edit(&mut self, cursor: (usize, usize)) {
+        self.history.begin(cursor);
+    }
+
+    /// Remember where the cursor ended up after the edits made so far.
+    pub fn end_edit(&mut self, cursor: (usize, usize)) {
+        self.history.end(cursor);
+    }
+
+    /// Close the current undo step.
+    pub fn seal(&mut self) {
+        self.history.seal();
+    }
+
+    /// Revert the most recent undo step, and return the cursor position
+    /// from before it was made.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.undo()?;
+        for edit in step.edits.iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        self.modified = true;
+        Some(step.before)
+    }
+
+    /// Re-apply the most recently undone step, and return the cursor
+    /// position from after it was made.
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.redo()?;
+        for edit in step.edits.iter() {
+            self.apply(edit);
+        }
+        self.modified = true;
+        Some(step.after)
+    }
+}
+
+/// Whether a part of the text between word boundaries is a word, for word
+/// motions and kills, rather than spaces or punctuation.
+fn is_word(part: &str) -> bool {
+    part.chars().any(char::is_alphanumeric)
+}
+
+/// The grapheme boundary after (or before) char x of line. The line is
+/// read a rope chunk at a time, so long lines aren't copied.
+fn grapheme_boundary(line: RopeSlice, x: usize, forward: bool) -> usize {
+    let byte = line.char_to_byte(x.min(line.len_chars()));
+    let mut cursor = GraphemeCursor::new(byte, line.len_bytes(), true);
+    let (mut chunk, mut start, _, _) = line.chunk_at_byte(byte);
+    loop {
+        let step = match forward {
+            true => cursor.next_boundary(chunk, start),
+            false => cursor.prev_boundary(chunk, start),
+        };
+        match step {
+            Ok(Some(b)) => return line.byte_to_char(b),
+            Ok(None) if forward => return line.len_chars(),
+            Ok(None) => return 0,
+            Err(GraphemeIncomplete::NextChunk) => {
+                (chunk, start, _, _) = line.chunk_at_byte(start + chunk.len());
+            }
+            Err(GraphemeIncomplete::PrevChunk) => {
+                (chunk, start, _, _) = line.chunk_at_byte(start - 1);
+            }
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let (context, at, _, _) = line.chunk_at_byte(n - 1);
+                cursor.provide_context(context, at);
+            }
+            Err(GraphemeIncomplete::InvalidOffset) => unreachable!(),
+        }
+    }
+}
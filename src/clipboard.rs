@@ -0,0 +1,160 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Where copied text goes, besides the kill ring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clipboard {
+    // Kill ring only
+    None,
+    // Ask the terminal to set the clipboard, with the OSC 52 escape
+    // sequence; works over SSH, but can't paste
+    Osc52,
+    WlCopy,
+    Xclip,
+    Pbcopy,
+}
+
+impl Clipboard {
+    /// Look up a clipboard by the name used in the config file.
+    pub fn parse(name: &str) -> Option<Clipboard> {
+        match name {
+            "auto" => Some(Clipboard::detect()),
+            "none" => Some(Clipboard::None),
+            "osc52" => Some(Clipboard::Osc52),
+            "wl-copy" => Some(Clipboard::WlCopy),
+            "xclip" => Some(Clipboard::Xclip),
+            "pbcopy" => Some(Clipboard::Pbcopy),
+            _ => None,
+        }
+    }
+
+    /// Pick a clipboard that should work here: the terminal's over SSH,
+    /// otherwise a helper program if one is installed.
+    pub fn detect() -> Clipboard {
+        let set = |var| env::var_os(var).is_some_and(|v| !v.is_empty());
+        if set("SSH_TTY") || set("SSH_CONNECTION") {
+            Clipboard::Osc52
+        } else if set("WAYLAND_DISPLAY") && in_path("wl-copy") {
+            Clipboard::WlCopy
+        } else if set("DISPLAY") && in_path("xclip") {
+            Clipboard::Xclip
+        } else if in_path("pbcopy") {
+            Clipboard::Pbcopy
+        } else {
+            Clipboard::Osc52
+        }
+    }
+
+    fn copy_command(self) -> Option<Command> {
+        let (prog, args): (&str, &[&str]) = match self {
+            Clipboard::None | Clipboard::Osc52 => return None,
+            Clipboard::WlCopy => ("wl-copy", &[]),
+            Clipboard::Xclip => ("xclip", &["-selection", "clipboard"]),
+            Clipboard::Pbcopy => ("pbcopy", &[]),
+        };
+        let mut cmd = Command::new(prog);
+        cmd.args(args);
+        Some(cmd)
+    }
+
+    fn paste_command(self) -> Option<Command> {
+        let (prog, args): (&str, &[&str]) = match self {
+            Clipboard::None | Clipboard::Osc52 => return None,
+            Clipboard::WlCopy => ("wl-paste", &["--no-newline"]),
+            Clipboard::Xclip => ("xclip", &["-selection", "clipboard", "-o"]),
+            Clipboard::Pbcopy => ("pbpaste", &[]),
+        };
+        let mut cmd = Command::new(prog);
+        cmd.args(args);
+        Some(cmd)
+    }
+
+    /// Put text on the clipboard. The OSC 52 sequence is written to term.
+    pub fn copy<W: Write>(self, text: &str, term: &mut W) -> io::Result<()> {
+        if self == Clipboard::Osc52 {
+            write!(term, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+            return term.flush();
+        }
+        let mut cmd = match self.copy_command() {
+            Some(cmd) => cmd,
+            None => return Ok(()),
+        };
+        // The helpers may stay around to serve the selection, so don't
+        // let them hold on to the terminal
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin")
+            .write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("copy failed: {}", status)));
+        }
+        Ok(())
+    }
+
+    /// The text on the clipboard, or None if it can't be read from here.
+    pub fn paste(self) -> Option<io::Result<String>> {
+        let mut cmd = self.paste_command()?;
+        Some(output(&mut cmd))
+    }
+}
+
+fn output(cmd: &mut Command) -> io::Result<String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut text = String::new();
+    child
+        .stdout
+        .take()
+        .expect("stdout")
+        .read_to_string(&mut text)?;
+    child.wait()?;
+    Ok(text)
+}
+
+fn in_path(prog: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths)
+            .any(|dir| Path::new(&dir).join(prog).is_file())
+    })
+}
+
+/// Pasted text with its line endings made into newlines: terminals send
+/// the ones in a bracketed paste as carriage returns.
+pub fn newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Standard base64, with padding.
+pub fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,265 @@
+use crate::display::LineNumbers;
+use crate::editor::{Command, Direction};
+
+/// Split s into words at whitespace. A word in double quotes may contain
+/// whitespace, and the escapes \" \\ \n \t \r \0 and \u{XXXX}.
+fn words(s: &str) -> Option<Vec<String>> {
+    let mut out = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut word = String::new();
+        match chars.peek() {
+            None => return Some(out),
+            Some('"') => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => word.push(match chars.next()? {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            '0' => '\0',
+                            'u' => {
+                                if chars.next()? != '{' {
+                                    return None;
+                                }
+                                let hex: String = chars
+                                    .by_ref()
+                                    .take_while(|c| *c != '}')
+                                    .collect();
+                                char::from_u32(
+                                    u32::from_str_radix(&hex, 16).ok()?,
+                                )?
+                            }
+                            c @ ('"' | '\\') => c,
+                            _ => return None,
+                        }),
+                        c => word.push(c),
+                    }
+                }
+                // A closing quote has to end the word
+                if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                    return None;
+                }
+            }
+            Some(_) => {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+            }
+        }
+        out.push(word);
+    }
+}
+
+/// Put s in double quotes, escaping it so that `words` reads it back.
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            c if c.is_control() => {
+                out.push_str(&format!("\\u{{{:x}}}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn display_direction(d: Direction) -> &'static str {
+    match d {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+/// Parse a command from its textual form: a name, followed by arguments
+/// separated by whitespace, e.g. "save", "move up", "move-to 10 4" or
+/// `insert "x"`. String arguments may be given in double quotes.
+pub fn parse(s: &str) -> Option<Command> {
+    let words = words(s)?;
+    let (name, args) = words.split_first()?;
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    Some(match (name.as_str(), args.as_slice()) {
+        ("nothing", []) => Command::Nothing,
+        ("insert", [s]) => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Command::InsertCharacter(c),
+                _ => return None,
+            }
+        }
+        ("insert-text", [s]) => Command::InsertText(s.to_string()),
+        ("insert-tab", []) => Command::InsertTab,
+        ("move", [d]) => Command::Move(parse_direction(d)?),
+        ("move-to", [x, y]) => {
+            Command::MoveTo(x.parse().ok()?, y.parse().ok()?)
+        }
+        ("click", [x, y]) => Command::Click(x.parse().ok()?, y.parse().ok()?),
+        ("move-page-up", []) => Command::MovePageUp,
+        ("move-page-down", []) => Command::MovePageDown,
+        ("move-line-home", []) => Command::MoveLineHome,
+        ("move-line-end", []) => Command::MoveLineEnd,
+        ("move-buffer-start", []) => Command::MoveBufferStart,
+        ("move-buffer-end", []) => Command::MoveBufferEnd,
+        ("move-word", [d]) => Command::MoveWord(parse_direction(d)?),
+        ("move-paragraph", [d]) => Command::MoveParagraph(parse_direction(d)?),
+        ("move-first-non-blank", []) => Command::MoveFirstNonBlank,
+        ("erase", [d]) => Command::Erase(parse_direction(d)?),
+        ("erase-word", [d]) => Command::EraseWord(parse_direction(d)?),
+        ("kill-line", []) => Command::KillLine,
+        ("kill-word", [d]) => Command::KillWord(parse_direction(d)?),
+        ("yank", []) => Command::Yank,
+        ("yank-pop", []) => Command::YankPop,
+        ("set-mark", []) => Command::SetMark,
+        ("select", [d]) => Command::Select(parse_direction(d)?),
+        ("cancel", []) => Command::Cancel,
+        ("delete-region", []) => Command::DeleteRegion,
+        ("copy-region", []) => Command::CopyRegion,
+        ("cut-region", []) => Command::CutRegion,
+        ("indent-region", []) => Command::IndentRegion,
+        ("dedent-region", []) => Command::DedentRegion,
+        ("goto-line", [n]) => Command::GotoLine(n.parse().ok()?),
+        ("save", []) => Command::Save,
+        ("save-as", [path]) => Command::SaveAs(path.to_string()),
+        ("open", [path]) => Command::Open(path.to_string()),
+        ("next-buffer", []) => Command::NextBuffer,
+        ("prev-buffer", []) => Command::PrevBuffer,
+        ("switch-buffer", [name]) => Command::SwitchBuffer(name.to_string()),
+        ("close-buffer", []) => Command::CloseBuffer,
+        ("list-buffers", []) => Command::ListBuffers,
+        ("split-horizontal", []) => Command::SplitHorizontal,
+        ("split-vertical", []) => Command::SplitVertical,
+        ("close-window", []) => Command::CloseWindow,
+        ("close-other-windows", []) => Command::CloseOtherWindows,
+        ("next-window", []) => Command::NextWindow,
+        ("focus-window", [d]) => Command::FocusWindow(parse_direction(d)?),
+        ("grow-window", []) => Command::GrowWindow,
+        ("shrink-window", []) => Command::ShrinkWindow,
+        ("toggle-whitespace", []) => Command::ToggleWhitespace,
+        ("line-numbers", [mode]) => {
+            Command::LineNumbers(LineNumbers::parse(mode)?)
+        }
+        ("undo", []) => Command::Undo,
+        ("redo", []) => Command::Redo,
+        ("search-forward", []) => Command::SearchForward,
+        ("search-backward", []) => Command::SearchBackward,
+        ("search-regex-forward", []) => Command::SearchRegexForward,
+        ("search-regex-backward", []) => Command::SearchRegexBackward,
+        ("query-replace", []) => Command::QueryReplace,
+        ("choose-line", []) => Command::ChooseLine,
+        ("choose-file", []) => Command::ChooseFile,
+        ("choose-save-as", []) => Command::ChooseSaveAs,
+        ("choose-buffer", []) => Command::ChooseBuffer,
+        ("describe-key", []) => Command::DescribeKey,
+        ("execute", []) => Command::Execute,
+        ("panic", [msg]) => Command::Panic(msg.to_string()),
+        ("exit", []) => Command::Exit,
+        _ => return None,
+    })
+}
+
+/// The textual form of a command, as understood by `parse`.
+pub fn display(cmd: &Command) -> String {
+    match cmd {
+        Command::Nothing => "nothing".into(),
+        Command::InsertCharacter(c) => {
+            format!("insert {}", quote(&c.to_string()))
+        }
+        Command::InsertText(s) => format!("insert-text {}", quote(s)),
+        Command::InsertTab => "insert-tab".into(),
+        Command::Move(d) => format!("move {}", display_direction(*d)),
+        Command::MoveTo(x, y) => format!("move-to {} {}", x, y),
+        Command::Click(x, y) => format!("click {} {}", x, y),
+        Command::MovePageUp => "move-page-up".into(),
+        Command::MovePageDown => "move-page-down".into(),
+        Command::MoveLineHome => "move-line-home".into(),
+        Command::MoveLineEnd => "move-line-end".into(),
+        Command::MoveBufferStart => "move-buffer-start".into(),
+        Command::MoveBufferEnd => "move-buffer-end".into(),
+        Command::MoveWord(d) => format!("move-word {}", display_direction(*d)),
+        Command::MoveParagraph(d) => {
+            format!("move-paragraph {}", display_direction(*d))
+        }
+        Command::MoveFirstNonBlank => "move-first-non-blank".into(),
+        Command::Erase(d) => format!("erase {}", display_direction(*d)),
+        Command::EraseWord(d) => {
+            format!("erase-word {}", display_direction(*d))
+        }
+        Command::KillLine => "kill-line".into(),
+        Command::KillWord(d) => {
+            format!("kill-word {}", display_direction(*d))
+        }
+        Command::Yank => "yank".into(),
+        Command::YankPop => "yank-pop".into(),
+        Command::SetMark => "set-mark".into(),
+        Command::Select(d) => format!("select {}", display_direction(*d)),
+        Command::Cancel => "cancel".into(),
+        Command::DeleteRegion => "delete-region".into(),
+        Command::CopyRegion => "copy-region".into(),
+        Command::CutRegion => "cut-region".into(),
+        Command::IndentRegion => "indent-region".into(),
+        Command::DedentRegion => "dedent-region".into(),
+        Command::GotoLine(n) => format!("goto-line {}", n),
+        Command::Save => "save".into(),
+        Command::SaveAs(path) => format!("save-as {}", quote(path)),
+        Command::Open(path) => format!("open {}", quote(path)),
+        Command::NextBuffer => "next-buffer".into(),
+        Command::PrevBuffer => "prev-buffer".into(),
+        Command::SwitchBuffer(name) => {
+            format!("switch-buffer {}", quote(name))
+        }
+        Command::CloseBuffer => "close-buffer".into(),
+        Command::ListBuffers => "list-buffers".into(),
+        Command::SplitHorizontal => "split-horizontal".into(),
+        Command::SplitVertical => "split-vertical".into(),
+        Command::CloseWindow => "close-window".into(),
+        Command::CloseOtherWindows => "close-other-windows".into(),
+        Command::NextWindow => "next-window".into(),
+        Command::FocusWindow(d) => {
+            format!("focus-window {}", display_direction(*d))
+        }
+        Command::GrowWindow => "grow-window".into(),
+        Command::ShrinkWindow => "shrink-window".into(),
+        Command::ToggleWhitespace => "toggle-whitespace".into(),
+        Command::LineNumbers(mode) => format!("line-numbers {}", mode.name()),
+        Command::Undo => "undo".into(),
+        Command::Redo => "redo".into(),
+        Command::SearchForward => "search-forward".into(),
+        Command::SearchBackward => "search-backward".into(),
+        Command::SearchRegexForward => "search-regex-forward".into(),
+        Command::SearchRegexBackward => "search-regex-backward".into(),
+        Command::QueryReplace => "query-replace".into(),
+        Command::ChooseLine => "choose-line".into(),
+        Command::ChooseFile => "choose-file".into(),
+        Command::ChooseSaveAs => "choose-save-as".into(),
+        Command::ChooseBuffer => "choose-buffer".into(),
+        Command::DescribeKey => "describe-key".into(),
+        Command::Execute => "execute".into(),
+        Command::Panic(msg) => format!("panic {}", quote(msg)),
+        Command::Exit => "exit".into(),
+    }
+}
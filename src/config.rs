@@ -0,0 +1,156 @@
+use anyhow::{anyhow, bail, Result};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::KeyEvent;
+
+use crate::commands;
+use crate::display::LineNumbers;
+use crate::editor::Command;
+use crate::keys;
+
+/// Where the config file lives: $XDG_CONFIG_HOME/rk/config, falling back to
+/// ~/.config/rk/config.
+pub fn path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("rk").join("config"))
+}
+
+/// Settings read from the config file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    // Name of the keymap to start with
+    pub keymap: Option<String>,
+    // Name of the clipboard to copy to
+    pub clipboard: Option<String>,
+    // Cells between tab stops
+    pub tab_width: Option<usize>,
+    // Indent with spaces rather than tabs
+    pub expand_tab: Option<bool>,
+    // Draw tabs and trailing spaces visibly
+    pub show_whitespace: Option<bool>,
+    // How to number lines
+    pub line_numbers: Option<LineNumbers>,
+    // Bindings on top of the keymap
+    pub bindings: Vec<(Vec<KeyEvent>, Command)>,
+}
+
+/// Read the config file at path. A missing file is the same as
+/// an empty one.
+pub fn load(path: &Path) -> Result<Config> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(&path.display().to_string(), &text),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            Ok(Config::default())
+        }
+        Err(err) => Err(anyhow!("{}: {}", path.display(), err)),
+    }
+}
+
+/// Parse a config file. Each line is either a setting: "keymap" and the
+/// name of the keymap to use, "clipboard" and the name of the clipboard to
+/// use, "tab-width" and a number, "expand-tab" or "show-whitespace" and yes
+/// or no, or "line-numbers" and off, absolute, relative or hybrid; or a key
+/// binding: a key, or a sequence of keys, then the command it runs. Blank
+/// lines and lines starting with # are skipped. Errors name the file and
+/// line they were found on.
+///
+///     keymap vi
+///     clipboard osc52
+///     tab-width 8
+///     expand-tab no
+///     line-numbers hybrid
+///     # Emacs-style movement
+///     c-p move up
+///     c-n move down
+///     c-z nothing
+///     a-i insert "\t"
+///     c-x c-s save
+pub fn parse(name: &str, text: &str) -> Result<Config> {
+    let mut config = Config::default();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(keymap) = line.strip_prefix("keymap ") {
+            config.keymap = Some(keymap.trim().into());
+            continue;
+        }
+        if let Some(clipboard) = line.strip_prefix("clipboard ") {
+            config.clipboard = Some(clipboard.trim().into());
+            continue;
+        }
+        if let Some(width) = line.strip_prefix("tab-width ") {
+            match width.trim().parse() {
+                Ok(width) if width > 0 => config.tab_width = Some(width),
+                _ => bail!("{}:{}: bad tab width {:?}", name, i + 1, width),
+            }
+            continue;
+        }
+        if let Some(expand) = line.strip_prefix("expand-tab ") {
+            config.expand_tab = Some(yes_no(expand, name, i)?);
+            continue;
+        }
+        if let Some(mode) = line.strip_prefix("line-numbers ") {
+            match LineNumbers::parse(mode.trim()) {
+                Some(mode) => config.line_numbers = Some(mode),
+                None => bail!(
+                    "{}:{}: unknown line numbers {:?}",
+                    name,
+                    i + 1,
+                    mode.trim()
+                ),
+            }
+            continue;
+        }
+        if let Some(show) = line.strip_prefix("show-whitespace ") {
+            config.show_whitespace = Some(yes_no(show, name, i)?);
+            continue;
+        }
+        // Words that read as keys may still be the start of the command
+        // (e.g. "insert"), so try every split between keys and command
+        let mut seq = Vec::new();
+        let mut rest = line;
+        let mut found = None;
+        while !rest.is_empty() {
+            let (word, after) = match rest.split_once(char::is_whitespace) {
+                Some((word, after)) => (word, after.trim_start()),
+                None => (rest, ""),
+            };
+            match keys::parse(word) {
+                Some(k) => seq.push(k),
+                None if seq.is_empty() => {
+                    bail!("{}:{}: bad key {:?}", name, i + 1, word)
+                }
+                None => break,
+            }
+            rest = after;
+            if let Some(cmd) = commands::parse(rest) {
+                found = Some((seq.clone(), cmd));
+                break;
+            }
+        }
+        match found {
+            Some(binding) => config.bindings.push(binding),
+            None if rest.is_empty() => {
+                bail!("{}:{}: no command for key {:?}", name, i + 1, line)
+            }
+            None => bail!("{}:{}: unknown command {:?}", name, i + 1, rest),
+        }
+    }
+    Ok(config)
+}
+
+fn yes_no(value: &str, name: &str, i: usize) -> Result<bool> {
+    match value.trim() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => bail!("{}:{}: expected yes or no", name, i + 1),
+    }
+}
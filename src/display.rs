@@ -0,0 +1,218 @@
+use anyhow::Result;
+use std::io::Write;
+
+use crossterm::{style, QueueableCommand};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// How a part of the text is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Face {
+    Normal,
+    // Control and invisible characters, shown by name
+    Control,
+    // Tabs and trailing spaces, when shown
+    Whitespace,
+    // A search match, and the one at the cursor
+    Match,
+    CurrentMatch,
+    Selection,
+    // Status line of the focused window, and of the others
+    Status,
+    StatusInactive,
+    // Window borders, and the ~ past the end of a buffer
+    Decoration,
+    // Line numbers
+    Gutter,
+}
+
+impl Face {
+    /// Switch to drawing text in this face.
+    pub fn queue<W: Write>(self, out: &mut W) -> Result<()> {
+        out.queue(style::SetAttribute(style::Attribute::NoReverse))?
+            .queue(style::ResetColor)?;
+        let bg = match self {
+            Face::Normal => return Ok(()),
+            Face::Control
+            | Face::Whitespace
+            | Face::Decoration
+            | Face::Gutter => {
+                let fg = match self {
+                    Face::Control => style::Color::Magenta,
+                    Face::Decoration => style::Color::Blue,
+                    _ => style::Color::DarkGrey,
+                };
+                out.queue(style::SetForegroundColor(fg))?;
+                return Ok(());
+            }
+            Face::Match => style::Color::DarkYellow,
+            Face::CurrentMatch => style::Color::Yellow,
+            Face::Status => style::Color::Blue,
+            Face::StatusInactive => style::Color::DarkGrey,
+            Face::Selection => {
+                out.queue(style::SetAttribute(style::Attribute::Reverse))?;
+                return Ok(());
+            }
+        };
+        out.queue(style::SetBackgroundColor(bg))?
+            .queue(style::SetForegroundColor(style::Color::Black))?;
+        Ok(())
+    }
+}
+
+/// How lines are numbered in the gutter left of the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineNumbers {
+    // No gutter
+    Off,
+    Absolute,
+    // Distance from the cursor's line
+    Relative,
+    // Relative, except the cursor's line, which has its absolute number
+    Hybrid,
+}
+
+impl LineNumbers {
+    /// Look up a numbering by the name used in commands and the config
+    /// file.
+    pub fn parse(name: &str) -> Option<LineNumbers> {
+        match name {
+            "off" => Some(LineNumbers::Off),
+            "absolute" => Some(LineNumbers::Absolute),
+            "relative" => Some(LineNumbers::Relative),
+            "hybrid" => Some(LineNumbers::Hybrid),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineNumbers::Off => "off",
+            LineNumbers::Absolute => "absolute",
+            LineNumbers::Relative => "relative",
+            LineNumbers::Hybrid => "hybrid",
+        }
+    }
+
+    /// The number shown for line y, with the cursor on line cy.
+    pub fn number(self, y: usize, cy: usize) -> usize {
+        match self {
+            LineNumbers::Hybrid if y == cy => y + 1,
+            LineNumbers::Relative | LineNumbers::Hybrid => y.abs_diff(cy),
+            LineNumbers::Off | LineNumbers::Absolute => y + 1,
+        }
+    }
+
+    /// Cells taken by the gutter for a buffer of the given number of lines:
+    /// room for the widest number, at least three digits so that it
+    /// doesn't change width while editing small files, and a space.
+    pub fn gutter_width(self, lines: usize) -> usize {
+        match self {
+            LineNumbers::Off => 0,
+            _ => lines.to_string().len().max(3) + 1,
+        }
+    }
+}
+
+/// A grapheme cluster as laid out on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyph {
+    // Char index of the cluster in its line
+    pub idx: usize,
+    // Screen column (cell) it starts at, counting from the start of the line
+    pub col: usize,
+    // Number of cells it takes
+    pub width: usize,
+    // What to print
+    pub text: String,
+    // Control for control characters, Whitespace for tabs and trailing
+    // spaces, Normal otherwise
+    pub face: Face,
+}
+
+impl Glyph {
+    /// What to print to make whitespace visible.
+    pub fn marker(&self) -> String {
+        match self.face {
+            Face::Whitespace if self.text == " " => String::from("\u{b7}"),
+            Face::Whitespace => format!("\u{bb}{}", &self.text[1..]),
+            _ => self.text.clone(),
+        }
+    }
+}
+
+/// How to show a grapheme cluster that isn't printed as is: control
+/// characters in caret notation (^M, ^[), others that take no cells by
+/// code point (<U+200B>).
+fn placeholder(g: &str) -> Option<String> {
+    let mut chars = g.chars();
+    let c = chars.next()?;
+    let lone = chars.next().is_none();
+    match c as u32 {
+        0..=0x1f if lone => Some(format!("^{}", (c as u8 + 0x40) as char)),
+        0x7f if lone => Some(String::from("^?")),
+        _ if (lone && c.is_control()) || g.width() == 0 => {
+            Some(format!("<U+{:04X}>", c as u32))
+        }
+        _ => None,
+    }
+}
+
+/// Lay out a line of text, one glyph per grapheme cluster. Double-width
+/// clusters take two cells, and tabs reach to the next multiple of
+/// tab_width. Control characters, and others the terminal would draw in no
+/// cells at all, are never printed as they are, but shown by name. Spaces
+/// from char index trailing on are trailing whitespace; the text may be cut
+/// short of the end of the line, so it can't tell by itself.
+pub fn glyphs(
+    line: &str,
+    tab_width: usize,
+    trailing: usize,
+) -> impl Iterator<Item = Glyph> + '_ {
+    let tab_width = tab_width.max(1);
+    let (mut idx, mut col) = (0, 0);
+    line.graphemes(true).map(move |g| {
+        let (text, face) = match placeholder(g) {
+            _ if g == "\t" => {
+                (" ".repeat(tab_width - col % tab_width), Face::Whitespace)
+            }
+            _ if g == " " && idx >= trailing => (g.into(), Face::Whitespace),
+            Some(name) => (name, Face::Control),
+            None => (g.into(), Face::Normal),
+        };
+        let width = match face {
+            Face::Normal => g.width(),
+            _ => text.len(),
+        };
+        let glyph = Glyph {
+            idx,
+            col,
+            width,
+            text,
+            face,
+        };
+        idx += g.chars().count();
+        col += width;
+        glyph
+    })
+}
+
+/// The screen column char x of the line is drawn at.
+pub fn col(line: &str, x: usize, tab_width: usize) -> usize {
+    let mut end = 0;
+    for g in glyphs(line, tab_width, usize::MAX) {
+        if g.idx >= x {
+            return g.col;
+        }
+        end = g.col + g.width;
+    }
+    end
+}
+
+/// The char index of the glyph drawn at screen column col, or the end of
+/// the line if it's narrower than that.
+pub fn idx(line: &str, col: usize, tab_width: usize) -> usize {
+    glyphs(line, tab_width, usize::MAX)
+        .find(|g| g.col + g.width > col)
+        .map_or_else(|| line.chars().count(), |g| g.idx)
+}
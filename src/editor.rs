@@ -1,27 +1,45 @@
-use anyhow::Result;
-use std::cmp::{max, min};
-use std::fs::File;
-use std::io::{self, BufRead, Write};
+use anyhow::{anyhow, bail, Result};
+use std::cmp::min;
+use std::io;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crossterm::{
-    event::{Event, KeyCode, KeyEvent, MouseEvent},
-    style,
-    terminal::{Clear, ClearType},
-    QueueableCommand,
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseEventKind,
 };
 
+use crate::buffer::Buffer;
+use crate::clipboard::{self, Clipboard};
+use crate::commands;
+use crate::config::{self, Config};
+use crate::display::{self, Face, LineNumbers};
+use crate::keymap::{Keymap, Lookup};
 use crate::keys;
+use crate::killring::KillRing;
+use crate::prompt::{self, Outcome, Prompt, PromptKind};
+use crate::screen::Screen;
+use crate::search::{self, Pattern, Replace, Search};
 use crate::tty;
 use crate::utils::*;
+use crate::vi::{self, InsertAt, Mode, Motion, Operator, Vi};
+use crate::window::{self, Layout, Rect, Split, Window};
 
 use std::collections::HashMap;
 
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+// How long to wait for the rest of a key sequence
+const CHORD_TIMEOUT: Duration = Duration::from_secs(3);
+
+// How many kills to keep for yanking
+const KILL_RING_SIZE: usize = 60;
+
+// Defaults for the tab settings
+const TAB_WIDTH: usize = 4;
+const EXPAND_TAB: bool = true;
 
 pub struct Exit;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Up,
     Down,
@@ -29,39 +47,158 @@ pub enum Direction {
     Right,
 }
 
+/// What to do with the text read through the prompt.
+#[derive(Debug, Clone)]
+enum Action {
+    Open,
+    SaveAs,
+    GotoLine,
+    SwitchBuffer,
+    ReplacePattern,
+    // Holds the pattern
+    ReplaceWith(String),
+    Execute,
+}
+
 #[derive(Debug)]
 pub struct Editor {
     // Frontend
     term: tty::Terminal,
+    screen: Screen,
+
+    // Open buffers
+    buffers: Vec<Buffer>,
+    // Windows, their arrangement on screen, and the focused one
+    windows: Vec<Window>,
+    layout: Layout,
+    focus: usize,
 
-    // Buffer / window with active file
-    fname: String,
-    lines: Vec<String>,
-    // Cursor position (in file)
-    cx: usize,
-    cy: usize,
-    // Offset (window scrolling)
-    ox: usize,
-    oy: usize,
+    // Text input in progress, if any, and what it is for
+    prompt: Option<(Prompt, Action)>,
+    // Earlier prompt inputs, oldest first
+    history: HashMap<PromptKind, Vec<String>>,
+    // Incremental search in progress, if any
+    search: Option<Search>,
+    // Query-replace in progress, if any
+    replace: Option<Replace>,
+
+    // Vi modal editing, when the vi keymap is in use
+    vi: Option<Vi>,
+    // Killed text, for yanking back
+    kills: KillRing,
+    // Where kills are copied to, and yanks pasted from
+    clipboard: Clipboard,
+    // Where the text last yanked went, for replacing it with YankPop
+    yanked: Option<((usize, usize), (usize, usize))>,
+    // The command run before the current one, other than Nothing
+    last_cmd: Command,
+    // The display column vertical movement keeps to, until the cursor moves
+    // some other way
+    goal: Option<usize>,
+    // Cells between tab stops, and whether indenting uses spaces rather
+    // than tabs
+    tab_width: usize,
+    expand_tab: bool,
+    // Draw tabs and trailing spaces visibly
+    show_whitespace: bool,
+    line_numbers: LineNumbers,
 
     // Status line
     message: String,
+    // Set by DescribeKey, so that the next key is described instead of
+    // being executed
+    describe: bool,
+    // Set by a command that refused to discard unsaved changes, so that
+    // repeating it goes ahead; reset by any other command
+    confirm: Option<&'static str>,
 
-    // Key bindings
-    keys: HashMap<KeyEvent, Command>,
+    // Key bindings, and the keys typed so far of a sequence that is not
+    // complete yet
+    keys: Keymap,
+    pending: Vec<KeyEvent>,
+    pending_since: Instant,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     Nothing,
     InsertCharacter(char),
+    // Pasted text, inserted in one go
+    InsertText(String),
+    // Indent the region if there is one, otherwise insert a tab, or spaces
+    // up to the next tab stop
+    InsertTab,
     Move(Direction),
     MoveTo(usize, usize),
+    // Screen coordinates, e.g. from the mouse
+    Click(usize, usize),
     MovePageUp,
     MovePageDown,
     MoveLineHome,
     MoveLineEnd,
+    MoveBufferStart,
+    MoveBufferEnd,
+    MoveWord(Direction),
+    // To the blank line before or after the paragraph
+    MoveParagraph(Direction),
+    MoveFirstNonBlank,
     Erase(Direction),
+    // Erase up to the start or end of a word
+    EraseWord(Direction),
+    // Remove the rest of the line, or the line break at its end, and save
+    // it in the kill ring
+    KillLine,
+    KillWord(Direction),
+    // Insert the text last killed
+    Yank,
+    // Replace the text just yanked with the kill before it
+    YankPop,
+    SetMark,
+    // Move, extending the region (as with shift and an arrow key)
+    Select(Direction),
+    // Drop the region
+    Cancel,
+    DeleteRegion,
+    CopyRegion,
+    CutRegion,
+    IndentRegion,
+    DedentRegion,
+    GotoLine(usize),
+    Save,
+    SaveAs(String),
+    Open(String),
+    NextBuffer,
+    PrevBuffer,
+    SwitchBuffer(String),
+    CloseBuffer,
+    ListBuffers,
+    SplitHorizontal,
+    SplitVertical,
+    CloseWindow,
+    CloseOtherWindows,
+    NextWindow,
+    FocusWindow(Direction),
+    GrowWindow,
+    ShrinkWindow,
+    // Show tabs and trailing spaces, or stop showing them
+    ToggleWhitespace,
+    LineNumbers(LineNumbers),
+    Undo,
+    Redo,
+    SearchForward,
+    SearchBackward,
+    SearchRegexForward,
+    SearchRegexBackward,
+    QueryReplace,
+    // Ask for the argument of the command of the same name
+    ChooseLine,
+    ChooseFile,
+    ChooseSaveAs,
+    ChooseBuffer,
+    // Show what the next key is bound to
+    DescribeKey,
+    // Ask for a command by name, and run it
+    Execute,
     Panic(String),
     Exit,
 }
@@ -69,51 +206,318 @@ pub enum Command {
 impl Editor {
     pub fn new(term: tty::Terminal) -> Editor {
         Editor {
-            cx: 0,
-            cy: 0,
-            ox: 0,
-            oy: 0,
-            term: term,
-            lines: Vec::new(),
+            screen: Screen::new(term.wx + 1, term.wy + 1),
+            term,
+            buffers: vec![Buffer::new()],
+            windows: vec![Window::new(0)],
+            layout: Layout::Window(0),
+            focus: 0,
+            prompt: None,
+            history: HashMap::new(),
+            search: None,
+            replace: None,
+            vi: None,
+            kills: KillRing::new(KILL_RING_SIZE),
+            clipboard: Clipboard::detect(),
+            yanked: None,
+            last_cmd: Command::Nothing,
+            goal: None,
+            tab_width: TAB_WIDTH,
+            expand_tab: EXPAND_TAB,
+            show_whitespace: false,
+            line_numbers: LineNumbers::Off,
             message: String::new(),
-            fname: String::from("*scratch*"),
+            describe: false,
+            confirm: None,
             keys: Editor::newkeys(),
+            pending: Vec::new(),
+            pending_since: Instant::now(),
         }
     }
 
-    fn newkeys() -> HashMap<KeyEvent, Command> {
-        let mut keys = HashMap::new();
-        keys.insert(keys::must_parse("c-q"), Command::Exit);
-        keys.insert(
-            keys::must_parse("m-q"),
+    fn newkeys() -> Keymap {
+        let mut keys = Keymap::new();
+        keys.bind(keys::must_parse_seq("c-q"), Command::Exit);
+        keys.bind(keys::must_parse_seq("c-s"), Command::Save);
+        keys.bind(keys::must_parse_seq("c-o"), Command::ChooseFile);
+        keys.bind(keys::must_parse_seq("a-g"), Command::ChooseLine);
+        keys.bind(keys::must_parse_seq("c-z"), Command::Undo);
+        keys.bind(keys::must_parse_seq("c-y"), Command::Redo);
+        keys.bind(keys::must_parse_seq("c-f"), Command::SearchForward);
+        keys.bind(keys::must_parse_seq("c-r"), Command::SearchBackward);
+        keys.bind(keys::must_parse_seq("a-s"), Command::SearchRegexForward);
+        keys.bind(keys::must_parse_seq("a-r"), Command::SearchRegexBackward);
+        keys.bind(keys::must_parse_seq("a-%"), Command::QueryReplace);
+        keys.bind(keys::must_parse_seq("a-n"), Command::NextBuffer);
+        keys.bind(keys::must_parse_seq("a-p"), Command::PrevBuffer);
+        keys.bind(keys::must_parse_seq("c-w"), Command::CloseBuffer);
+        keys.bind(keys::must_parse_seq("c-b"), Command::ChooseBuffer);
+        keys.bind(keys::must_parse_seq("a-B"), Command::ListBuffers);
+        keys.bind(keys::must_parse_seq("a-2"), Command::SplitHorizontal);
+        keys.bind(keys::must_parse_seq("a-3"), Command::SplitVertical);
+        keys.bind(keys::must_parse_seq("a-0"), Command::CloseWindow);
+        keys.bind(keys::must_parse_seq("a-1"), Command::CloseOtherWindows);
+        keys.bind(keys::must_parse_seq("a-o"), Command::NextWindow);
+        keys.bind(
+            keys::must_parse_seq("a-h"),
+            Command::FocusWindow(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("a-j"),
+            Command::FocusWindow(Direction::Down),
+        );
+        keys.bind(
+            keys::must_parse_seq("a-k"),
+            Command::FocusWindow(Direction::Up),
+        );
+        keys.bind(
+            keys::must_parse_seq("a-l"),
+            Command::FocusWindow(Direction::Right),
+        );
+        keys.bind(keys::must_parse_seq("f1"), Command::DescribeKey);
+        keys.bind(keys::must_parse_seq("a-x"), Command::Execute);
+        keys.bind(keys::must_parse_seq("c-space"), Command::SetMark);
+        keys.bind(keys::must_parse_seq("c-g"), Command::Cancel);
+        keys.bind(keys::must_parse_seq("esc"), Command::Cancel);
+        keys.bind(keys::must_parse_seq("c-c"), Command::CopyRegion);
+        keys.bind(keys::must_parse_seq("c-x"), Command::CutRegion);
+        keys.bind(keys::must_parse_seq("c-v"), Command::Yank);
+        keys.bind(keys::must_parse_seq("a-y"), Command::YankPop);
+        keys.bind(keys::must_parse_seq("c-k"), Command::KillLine);
+        keys.bind(
+            keys::must_parse_seq("a-d"),
+            Command::KillWord(Direction::Right),
+        );
+        keys.bind(
+            keys::must_parse_seq("a-backspace"),
+            Command::KillWord(Direction::Left),
+        );
+        keys.bind(keys::must_parse_seq("tab"), Command::InsertTab);
+        keys.bind(keys::must_parse_seq("backtab"), Command::DedentRegion);
+        keys.bind(
+            keys::must_parse_seq("s-up"),
+            Command::Select(Direction::Up),
+        );
+        keys.bind(
+            keys::must_parse_seq("s-down"),
+            Command::Select(Direction::Down),
+        );
+        keys.bind(
+            keys::must_parse_seq("s-left"),
+            Command::Select(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("s-right"),
+            Command::Select(Direction::Right),
+        );
+        keys.bind(keys::must_parse_seq("a-+"), Command::GrowWindow);
+        keys.bind(keys::must_parse_seq("a-_"), Command::ShrinkWindow);
+        keys.bind(
+            keys::must_parse_seq("m-q"),
             Command::Panic("forced panic".into()),
         );
-        keys.insert(keys::must_parse("up"), Command::Move(Direction::Up));
-        keys.insert(keys::must_parse("down"), Command::Move(Direction::Down));
-        keys.insert(keys::must_parse("left"), Command::Move(Direction::Left));
-        keys.insert(
-            keys::must_parse("right"),
+        keys.bind(keys::must_parse_seq("up"), Command::Move(Direction::Up));
+        keys.bind(
+            keys::must_parse_seq("down"),
+            Command::Move(Direction::Down),
+        );
+        keys.bind(
+            keys::must_parse_seq("left"),
+            Command::Move(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("right"),
             Command::Move(Direction::Right),
         );
-        keys.insert(keys::must_parse("a-b"), Command::Move(Direction::Left));
-        keys.insert(keys::must_parse("a-f"), Command::Move(Direction::Right));
-        keys.insert(keys::must_parse("c-m"), Command::InsertCharacter('\n'));
-        keys.insert(keys::must_parse("enter"), Command::InsertCharacter('\n'));
-        keys.insert(keys::must_parse("pageup"), Command::MovePageUp);
-        keys.insert(keys::must_parse("pagedown"), Command::MovePageDown);
-        keys.insert(keys::must_parse("home"), Command::MoveLineHome);
-        keys.insert(keys::must_parse("end"), Command::MoveLineEnd);
-        keys.insert(
-            keys::must_parse("backspace"),
+        keys.bind(
+            keys::must_parse_seq("a-b"),
+            Command::MoveWord(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("a-f"),
+            Command::MoveWord(Direction::Right),
+        );
+        keys.bind(
+            keys::must_parse_seq("c-left"),
+            Command::MoveWord(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("c-right"),
+            Command::MoveWord(Direction::Right),
+        );
+        keys.bind(
+            keys::must_parse_seq("c-up"),
+            Command::MoveParagraph(Direction::Up),
+        );
+        keys.bind(
+            keys::must_parse_seq("c-down"),
+            Command::MoveParagraph(Direction::Down),
+        );
+        keys.bind(keys::must_parse_seq("a-<"), Command::MoveBufferStart);
+        keys.bind(keys::must_parse_seq("a->"), Command::MoveBufferEnd);
+        keys.bind(keys::must_parse_seq("a-m"), Command::MoveFirstNonBlank);
+        keys.bind(keys::must_parse_seq("c-m"), Command::InsertCharacter('\n'));
+        keys.bind(
+            keys::must_parse_seq("enter"),
+            Command::InsertCharacter('\n'),
+        );
+        keys.bind(keys::must_parse_seq("pageup"), Command::MovePageUp);
+        keys.bind(keys::must_parse_seq("pagedown"), Command::MovePageDown);
+        keys.bind(keys::must_parse_seq("home"), Command::MoveLineHome);
+        keys.bind(keys::must_parse_seq("end"), Command::MoveLineEnd);
+        keys.bind(
+            keys::must_parse_seq("backspace"),
             Command::Erase(Direction::Left),
         );
-        keys.insert(
-            keys::must_parse("delete"),
+        keys.bind(
+            keys::must_parse_seq("delete"),
             Command::Erase(Direction::Right),
         );
+        // Most terminals only tell c-backspace from backspace (and from c-h)
+        // with the kitty keyboard protocol, but c-delete is widely sent
+        keys.bind(
+            keys::must_parse_seq("c-backspace"),
+            Command::EraseWord(Direction::Left),
+        );
+        keys.bind(
+            keys::must_parse_seq("c-delete"),
+            Command::EraseWord(Direction::Right),
+        );
+        keys
+    }
+
+    /// Emacs-style bindings, as an alternative to `newkeys`.
+    fn emacskeys() -> Keymap {
+        let mut keys = Keymap::new();
+        let bindings = [
+            ("c-x c-c", Command::Exit),
+            ("c-x c-s", Command::Save),
+            ("c-x c-w", Command::ChooseSaveAs),
+            ("c-x c-f", Command::ChooseFile),
+            ("c-x b", Command::ChooseBuffer),
+            ("c-x c-b", Command::ListBuffers),
+            ("c-x k", Command::CloseBuffer),
+            ("c-x right", Command::NextBuffer),
+            ("c-x left", Command::PrevBuffer),
+            ("c-x 2", Command::SplitHorizontal),
+            ("c-x 3", Command::SplitVertical),
+            ("c-x 0", Command::CloseWindow),
+            ("c-x 1", Command::CloseOtherWindows),
+            ("c-x o", Command::NextWindow),
+            ("c-x ^", Command::GrowWindow),
+            ("c-x u", Command::Undo),
+            // c-/ and c-_ both arrive as c-7
+            ("c-7", Command::Undo),
+            ("c-x r", Command::Redo),
+            ("c-s", Command::SearchForward),
+            ("c-r", Command::SearchBackward),
+            ("c-a-s", Command::SearchRegexForward),
+            ("c-a-r", Command::SearchRegexBackward),
+            ("a-%", Command::QueryReplace),
+            ("a-g g", Command::ChooseLine),
+            ("a-g a-g", Command::ChooseLine),
+            ("a-x", Command::Execute),
+            ("f1", Command::DescribeKey),
+            ("c-f", Command::Move(Direction::Right)),
+            ("c-b", Command::Move(Direction::Left)),
+            ("c-n", Command::Move(Direction::Down)),
+            ("c-p", Command::Move(Direction::Up)),
+            ("c-a", Command::MoveLineHome),
+            ("c-e", Command::MoveLineEnd),
+            ("c-v", Command::MovePageDown),
+            ("a-v", Command::MovePageUp),
+            ("a-<", Command::MoveBufferStart),
+            ("a->", Command::MoveBufferEnd),
+            ("a-b", Command::MoveWord(Direction::Left)),
+            ("a-f", Command::MoveWord(Direction::Right)),
+            ("a-{", Command::MoveParagraph(Direction::Up)),
+            ("a-}", Command::MoveParagraph(Direction::Down)),
+            ("c-up", Command::MoveParagraph(Direction::Up)),
+            ("c-down", Command::MoveParagraph(Direction::Down)),
+            ("a-m", Command::MoveFirstNonBlank),
+            ("c-d", Command::Erase(Direction::Right)),
+            ("c-k", Command::KillLine),
+            ("c-y", Command::Yank),
+            ("a-y", Command::YankPop),
+            ("a-d", Command::KillWord(Direction::Right)),
+            ("a-backspace", Command::KillWord(Direction::Left)),
+            ("c-space", Command::SetMark),
+            ("c-g", Command::Cancel),
+            ("c-w", Command::CutRegion),
+            ("a-w", Command::CopyRegion),
+            ("tab", Command::InsertTab),
+            ("c-x tab", Command::IndentRegion),
+            ("c-x backtab", Command::DedentRegion),
+            ("s-up", Command::Select(Direction::Up)),
+            ("s-down", Command::Select(Direction::Down)),
+            ("s-left", Command::Select(Direction::Left)),
+            ("s-right", Command::Select(Direction::Right)),
+            ("up", Command::Move(Direction::Up)),
+            ("down", Command::Move(Direction::Down)),
+            ("left", Command::Move(Direction::Left)),
+            ("right", Command::Move(Direction::Right)),
+            ("pageup", Command::MovePageUp),
+            ("pagedown", Command::MovePageDown),
+            ("home", Command::MoveLineHome),
+            ("end", Command::MoveLineEnd),
+            ("c-m", Command::InsertCharacter('\n')),
+            ("enter", Command::InsertCharacter('\n')),
+            ("backspace", Command::Erase(Direction::Left)),
+            ("delete", Command::Erase(Direction::Right)),
+        ];
+        for (seq, cmd) in bindings {
+            keys.bind(keys::must_parse_seq(seq), cmd);
+        }
         keys
     }
 
+    /// Switch to the named keymap: "default", "emacs", or "vi" for modal
+    /// editing.
+    pub fn set_keymap(&mut self, name: &str) -> Result<()> {
+        self.keys = match name {
+            "default" | "vi" => Editor::newkeys(),
+            "emacs" => Editor::emacskeys(),
+            _ => bail!("unknown keymap: {}", name),
+        };
+        self.vi = match name {
+            "vi" => Some(Vi::new()),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Apply the user's config file, if there is one. A keymap given here
+    /// (e.g. on the command line) wins over the one named in the file.
+    pub fn load_config(&mut self, keymap: Option<&str>) -> Result<()> {
+        let config = match config::path() {
+            Some(path) => config::load(&path)?,
+            None => Config::default(),
+        };
+        if let Some(name) = keymap.or(config.keymap.as_deref()) {
+            self.set_keymap(name)?;
+        }
+        if let Some(name) = config.clipboard {
+            self.clipboard = Clipboard::parse(&name)
+                .ok_or_else(|| anyhow!("unknown clipboard: {}", name))?;
+        }
+        if let Some(width) = config.tab_width {
+            self.tab_width = width;
+        }
+        if let Some(expand) = config.expand_tab {
+            self.expand_tab = expand;
+        }
+        if let Some(show) = config.show_whitespace {
+            self.show_whitespace = show;
+        }
+        if let Some(mode) = config.line_numbers {
+            self.line_numbers = mode;
+        }
+        for (seq, cmd) in config.bindings {
+            self.keys.bind(seq, cmd);
+        }
+        Ok(())
+    }
+
     pub fn init(&mut self) -> Result<()> {
         self.term.init()?;
         self.update_screen()?;
@@ -122,8 +526,12 @@ impl Editor {
 
     pub fn update(&mut self) -> Result<Option<Exit>> {
         let cmd = self.update_input()?;
+        let last = cmd.clone();
         let status = self.exec_cmd(cmd)?;
-        self.scroll_to_cursor();
+        if last != Command::Nothing {
+            self.last_cmd = last;
+        }
+        self.vi_clamp();
         self.update_screen()?;
         Ok(status)
     }
@@ -133,63 +541,1270 @@ impl Editor {
         Ok(())
     }
 
-    pub fn open(&mut self, fname: &Path) -> io::Result<()> {
-        let file = io::BufReader::new(File::open(fname)?);
-        for line in file.lines().map(|l| l.unwrap()) {
-            self.lines.push(line);
-        }
-        if let Some(bname) = fname.file_name() {
-            self.fname = String::from(bname.to_str().unwrap());
+    pub fn open(&mut self, path: &Path) -> io::Result<()> {
+        if let Ok(path) = std::fs::canonicalize(path) {
+            let open = self
+                .buffers
+                .iter()
+                .position(|b| b.path == Some(path.clone()));
+            if let Some(idx) = open {
+                self.switch_to(idx);
+                return Ok(());
+            }
         }
+        let mut buf = Buffer::open(path)?;
+        buf.name = self.unique_name(&buf.name);
+        self.buffers.push(buf);
+        self.switch_to(self.buffers.len() - 1);
         Ok(())
     }
 
+    fn buf(&self) -> &Buffer {
+        &self.buffers[self.win().buf]
+    }
+
+    fn buf_mut(&mut self) -> &mut Buffer {
+        let idx = self.win().buf;
+        &mut self.buffers[idx]
+    }
+
+    fn win(&self) -> &Window {
+        &self.windows[self.focus]
+    }
+
+    fn win_mut(&mut self) -> &mut Window {
+        &mut self.windows[self.focus]
+    }
+
+    fn cursor(&self) -> (usize, usize) {
+        (self.win().cx, self.win().cy)
+    }
+
+    fn set_cursor(&mut self, x: usize, y: usize) {
+        let win = self.win_mut();
+        win.cx = x;
+        win.cy = y;
+    }
+
+    fn unique_name(&self, name: &str) -> String {
+        let mut candidate = String::from(name);
+        let mut i = 1;
+        while self.buffers.iter().any(|b| b.name == candidate) {
+            i += 1;
+            candidate = format!("{}<{}>", name, i);
+        }
+        candidate
+    }
+
+    /// Show buffer idx in the focused window.
+    fn switch_to(&mut self, idx: usize) {
+        let win = self.win().clone();
+        let buf = &mut self.buffers[win.buf];
+        buf.cursor = (win.cx, win.cy);
+        buf.offset = (win.ox, win.oy);
+        self.show_buffer(self.focus, idx);
+    }
+
+    /// Point window w at buffer idx, restoring the position last seen in
+    /// that buffer.
+    fn show_buffer(&mut self, w: usize, idx: usize) {
+        self.point_window(w, idx);
+        self.clamp_cursors();
+    }
+
+    /// Point window w at buffer idx, without checking that the positions of
+    /// the windows are still within their buffers.
+    fn point_window(&mut self, w: usize, idx: usize) {
+        let buf = &self.buffers[idx];
+        let (cursor, offset) = (buf.cursor, buf.offset);
+        let win = &mut self.windows[w];
+        win.buf = idx;
+        win.ox = offset.0;
+        win.oy = offset.1;
+        win.cx = cursor.0;
+        win.cy = cursor.1;
+    }
+
+    fn close_buffer(&mut self) {
+        let closed = self.win().buf;
+        self.buffers.remove(closed);
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::new());
+        }
+        // Renumber every window before any is pointed elsewhere, so that
+        // none is left pointing past the end of the buffers
+        let mut orphans = Vec::new();
+        for (w, win) in self.windows.iter_mut().enumerate() {
+            if win.buf == closed {
+                orphans.push(w);
+            } else if win.buf > closed {
+                win.buf -= 1;
+            }
+        }
+        let next = min(closed, self.buffers.len() - 1);
+        for w in orphans {
+            // Nothing to save from the closed buffer
+            self.point_window(w, next);
+        }
+        self.clamp_cursors();
+    }
+
+    /// Keep every window's cursor within its buffer, e.g. after the text
+    /// was changed through another window.
+    fn clamp_cursors(&mut self) {
+        for win in self.windows.iter_mut() {
+            let buf = &self.buffers[win.buf];
+            win.cy = min(win.cy, buf.len_lines() - 1);
+            win.cx = min(win.cx, buf.line_len(win.cy));
+        }
+    }
+
+    /// Screen area available to windows. Each window uses its last row for
+    /// its status line.
+    fn screen_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            w: self.term.wx + 1,
+            h: self.term.wy + 1,
+        }
+    }
+
+    fn win_rect(&self, w: usize) -> Rect {
+        self.layout
+            .rects(self.screen_area())
+            .into_iter()
+            .find(|(i, _)| *i == w)
+            .map(|(_, r)| r)
+            .expect("window in layout")
+    }
+
+    /// The part of window w's rect that shows text: all of it but the
+    /// gutter on the left and the status line. The gutter is left out of
+    /// windows too narrow for it.
+    fn text_rect(&self, w: usize, rect: Rect) -> Rect {
+        let lines = self.buffers[self.windows[w].buf].len_lines();
+        let gutter = match self.line_numbers.gutter_width(lines) {
+            g if g < rect.w => g,
+            _ => 0,
+        };
+        Rect {
+            x: rect.x + gutter,
+            y: rect.y,
+            w: rect.w - gutter,
+            h: rect.h.saturating_sub(1),
+        }
+    }
+
+    fn split_window(&mut self, split: Split) {
+        let mut win = self.win().clone();
+        let rect = self.win_rect(self.focus);
+        let (size, min_size) = match split {
+            Split::Horizontal => (rect.h, 2 * window::MIN_HEIGHT),
+            Split::Vertical => (rect.w, 2 * window::MIN_WIDTH + 1),
+        };
+        if size < min_size {
+            self.message = "window too small to split".into();
+            return;
+        }
+        win.ox = 0;
+        self.windows.push(win);
+        let new = self.windows.len() - 1;
+        self.layout.split(self.focus, new, split);
+    }
+
+    fn close_window(&mut self, w: usize) {
+        if self.windows.len() == 1 {
+            self.message = "cannot close the only window".into();
+            return;
+        }
+        self.windows.remove(w);
+        self.layout.remove(w);
+        if self.focus > w || self.focus == self.windows.len() {
+            self.focus -= 1;
+        }
+    }
+
+    /// Focus the window at screen position (x, y), and return the command
+    /// that moves its cursor there.
+    fn focus_at(&mut self, x: usize, y: usize) -> Command {
+        let hit = self.layout.rects(self.screen_area()).into_iter().find(
+            |(_, r)| r.x <= x && x < r.x + r.w && r.y <= y && y < r.y + r.h,
+        );
+        match hit {
+            Some((w, r)) => {
+                self.focus = w;
+                // Clicking the status line only focuses the window
+                if y + 1 < r.y + r.h {
+                    let text = self.text_rect(w, r);
+                    let win = self.win();
+                    // Clicking the gutter goes to the start of the line
+                    let x = x.max(text.x);
+                    let (col, y) = (win.ox + x - text.x, win.oy + y - r.y);
+                    let y = min(y, self.buf().len_lines() - 1);
+                    let line = Editor::line_to_col(
+                        self.buf(),
+                        y,
+                        col,
+                        self.tab_width,
+                    );
+                    Command::MoveTo(
+                        display::idx(&line, col, self.tab_width),
+                        y,
+                    )
+                } else {
+                    Command::Nothing
+                }
+            }
+            None => Command::Nothing,
+        }
+    }
+
+    /// Names of buffers visiting a file, with changes not yet saved.
+    fn unsaved(&self) -> Vec<&str> {
+        self.buffers
+            .iter()
+            .filter(|b| b.modified && b.path.is_some())
+            .map(|b| b.name.as_str())
+            .collect()
+    }
+
     fn update_input(&mut self) -> Result<Command> {
         let ev = self.term.get_event()?;
-        self.message = String::from(format!("rk v{} ev{:?}", VERSION, ev));
+        self.message = format!("rk v{} ev{:?}", VERSION, ev);
         Ok(match ev {
-            None => Command::Nothing,
-            Some(Event::Key(k)) => {
-                if let Some(cmd) = self.keys.get(&k) {
-                    cmd.clone()
-                } else {
-                    if k.modifiers.is_empty() {
-                        if let KeyCode::Char(c) = k.code {
-                            return Ok(Command::InsertCharacter(c));
-                        }
-                    }
-                    self.message =
-                        format!("key not bound: {}", keys::display(k));
-                    Command::Nothing
+            None => {
+                if !self.pending.is_empty()
+                    && self.pending_since.elapsed() >= CHORD_TIMEOUT
+                {
+                    self.message = format!(
+                        "{} timed out",
+                        keys::display_seq(&self.pending)
+                    );
+                    self.pending.clear();
                 }
+                Command::Nothing
             }
-            Some(Event::Resize(_, _)) => Command::Nothing,
-            Some(Event::Mouse(m)) => match m {
-                MouseEvent::Down(_, x, y, _) => {
-                    Command::MoveTo(x as usize, y as usize)
+            Some(Event::Key(k)) => self.key(k),
+            Some(Event::Paste(text)) => self.paste(text),
+            Some(Event::Mouse(m)) => match m.kind {
+                MouseEventKind::Down(_)
+                | MouseEventKind::Up(_)
+                | MouseEventKind::Drag(_) => {
+                    Command::Click(m.column as usize, m.row as usize)
                 }
-                MouseEvent::Up(_, x, y, _) => {
-                    Command::MoveTo(x as usize, y as usize)
+                MouseEventKind::ScrollUp => Command::MovePageUp,
+                MouseEventKind::ScrollDown => Command::MovePageDown,
+                _ => Command::Nothing,
+            },
+            Some(_) => Command::Nothing,
+        })
+    }
+
+    fn key(&mut self, k: KeyEvent) -> Command {
+        match k {
+            _ if self.prompt.is_some() => self.prompt_key(k),
+            _ if self.search.is_some() => self.search_key(k),
+            _ if self.replace.is_some() => self.replace_key(k),
+            _ if !self.pending.is_empty() => self.key_command(k),
+            _ if self.vi_mode() == Some(Mode::Insert)
+                && k.code == KeyCode::Esc =>
+            {
+                let (x, y) = self.cursor();
+                self.set_cursor(self.buf().prev_grapheme(x, y), y);
+                self.vi_set_mode(Mode::Normal);
+                Command::Nothing
+            }
+            _ if self.vi_mode().is_some_and(|m| m != Mode::Insert) => {
+                self.vi_key(k)
+            }
+            _ => self.key_command(k),
+        }
+    }
+
+    /// Handle pasted text: it goes into the buffer as it is, whatever the
+    /// keys in it are bound to.
+    fn paste(&mut self, text: String) -> Command {
+        if self.prompt.is_some() || self.search.is_some() {
+            // Prompts only take a single line
+            for c in text.chars().filter(|c| !c.is_control()) {
+                self.key(KeyCode::Char(c).into());
+            }
+            return Command::Nothing;
+        }
+        if self.replace.is_some()
+            || self.vi_mode().is_some_and(|m| m != Mode::Insert)
+        {
+            // Keys there are commands, not text
+            return Command::Nothing;
+        }
+        self.pending.clear();
+        Command::InsertText(clipboard::newlines(&text))
+    }
+
+    /// Look up the command bound to k, following any keys typed before
+    /// it that make up the start of a sequence.
+    fn key_command(&mut self, k: KeyEvent) -> Command {
+        if !self.pending.is_empty() && k == keys::must_parse("c-g") {
+            self.pending.clear();
+            self.describe = false;
+            self.message = "quit".into();
+            return Command::Nothing;
+        }
+        self.pending.push(k);
+        let seq = keys::display_seq(&self.pending);
+        let cmd = match self.keys.lookup(&self.pending) {
+            Lookup::Prefix => {
+                self.pending_since = Instant::now();
+                return Command::Nothing;
+            }
+            Lookup::Command(cmd) => cmd.clone(),
+            Lookup::Unbound => match (k.code, self.pending.len()) {
+                (KeyCode::Char(c), 1) if k.modifiers.is_empty() => {
+                    Command::InsertCharacter(c)
                 }
-                MouseEvent::Drag(_, x, y, _) => {
-                    Command::MoveTo(x as usize, y as usize)
+                _ => {
+                    self.pending.clear();
+                    self.describe = false;
+                    self.message = format!("key not bound: {}", seq);
+                    return Command::Nothing;
                 }
-                MouseEvent::ScrollUp(_, _, _) => Command::MovePageUp,
-                MouseEvent::ScrollDown(_, _, _) => Command::MovePageDown,
             },
-        })
+        };
+        self.pending.clear();
+        if self.describe {
+            self.describe = false;
+            self.message = format!("{} runs {}", seq, commands::display(&cmd));
+            return Command::Nothing;
+        }
+        cmd
+    }
+
+    fn history(&self, kind: PromptKind) -> &[String] {
+        self.history.get(&kind).map_or(&[], |h| h.as_slice())
+    }
+
+    /// Start reading text for action in the status line.
+    fn ask(&mut self, kind: PromptKind, label: &str, action: Action) {
+        self.prompt = Some((Prompt::new(kind, label), action));
+    }
+
+    /// Candidates for completing text in a prompt of the given kind.
+    fn complete(&self, kind: PromptKind, text: &str) -> Vec<String> {
+        match kind {
+            PromptKind::File => prompt::complete_path(text),
+            PromptKind::Buffer => self
+                .buffers
+                .iter()
+                .filter(|b| b.name.starts_with(text))
+                .map(|b| b.name.clone())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Handle a key while reading text in the prompt.
+    fn prompt_key(&mut self, k: KeyEvent) -> Command {
+        let (mut prompt, action) = self.prompt.take().expect("prompt");
+        let kind = prompt.kind;
+        let outcome = prompt
+            .key(k, self.history(kind), &|text| self.complete(kind, text));
+        match outcome {
+            Outcome::Pending | Outcome::Ignored => {
+                self.prompt = Some((prompt, action));
+                Command::Nothing
+            }
+            Outcome::Cancelled => {
+                self.message = "cancelled".into();
+                Command::Nothing
+            }
+            Outcome::Done(text) => {
+                prompt::remember(self.history.entry(kind).or_default(), &text);
+                self.prompt_done(action, text)
+            }
+        }
+    }
+
+    /// Act on text read through the prompt.
+    fn prompt_done(&mut self, action: Action, text: String) -> Command {
+        if text.is_empty() {
+            return Command::Nothing;
+        }
+        match action {
+            Action::Execute => self.execute(&text),
+            Action::Open => Command::Open(text),
+            Action::SaveAs => Command::SaveAs(text),
+            Action::SwitchBuffer => Command::SwitchBuffer(text),
+            Action::GotoLine => match text.trim().parse() {
+                Ok(n) => Command::GotoLine(n),
+                Err(_) => {
+                    self.message = format!("not a line number: {}", text);
+                    Command::Nothing
+                }
+            },
+            Action::ReplacePattern => {
+                if let Err(err) = regex::Regex::new(&text) {
+                    self.message = format!("invalid regexp: {}", err);
+                    return Command::Nothing;
+                }
+                let label = format!("Query replace regexp {} with: ", text);
+                self.ask(
+                    PromptKind::ReplaceWith,
+                    &label,
+                    Action::ReplaceWith(text),
+                );
+                Command::Nothing
+            }
+            Action::ReplaceWith(pattern) => {
+                let re = regex::Regex::new(&pattern).expect("checked");
+                let cursor = self.cursor();
+                self.buf_mut().seal();
+                self.buf_mut().begin_edit(cursor);
+                let from = self.buf().pos_to_char(cursor.0, cursor.1);
+                let haystack = search::Haystack::default();
+                self.replace_next(re, text, (from, None), 0, haystack);
+                Command::Nothing
+            }
+        }
+    }
+
+    /// Handle a key while searching. Keys that have no meaning in a search
+    /// end it, and are then executed as usual.
+    fn search_key(&mut self, k: KeyEvent) -> Command {
+        let mut search = self.search.take().expect("search");
+        let repeat = match self.keys.lookup(&[k]) {
+            Lookup::Command(cmd) => Some(cmd),
+            _ => None,
+        };
+        let repeat = match repeat {
+            Some(Command::SearchForward | Command::SearchRegexForward) => {
+                Some(true)
+            }
+            Some(Command::SearchBackward | Command::SearchRegexBackward) => {
+                Some(false)
+            }
+            _ => None,
+        };
+        if let Some(forward) = repeat {
+            // Repeating the search with no query recalls the last one
+            if search.query().is_empty() {
+                if let Some(last) = self.history(PromptKind::Search).last() {
+                    search.prompt.set_text(&last.clone());
+                }
+            }
+            search.forward = forward;
+            self.search_step(&mut search, true);
+            search.update_prompt();
+            self.search = Some(search);
+            return Command::Nothing;
+        }
+        let before = search.query().to_string();
+        match search
+            .prompt
+            .key(k, self.history(PromptKind::Search), &|_| Vec::new())
+        {
+            Outcome::Pending => {
+                if search.query() != before {
+                    // Keep the match while the query grows, start over from
+                    // where the search began otherwise
+                    if !search.query().starts_with(&before) {
+                        let origin = search.origin.clone();
+                        self.set_cursor(origin.cx, origin.cy);
+                        search.wrapped = false;
+                    }
+                    self.search_step(&mut search, false);
+                }
+            }
+            Outcome::Done(query) => {
+                let history = self.history.entry(PromptKind::Search);
+                prompt::remember(history.or_default(), &query);
+                return Command::Nothing;
+            }
+            Outcome::Cancelled => {
+                *self.win_mut() = search.origin;
+                self.message = "search cancelled".into();
+                return Command::Nothing;
+            }
+            Outcome::Ignored => {
+                let history = self.history.entry(PromptKind::Search);
+                prompt::remember(history.or_default(), search.query());
+                return self.key_command(k);
+            }
+        }
+        search.update_prompt();
+        self.search = Some(search);
+        Command::Nothing
+    }
+
+    /// Move the cursor to the next match of the search query; with next
+    /// unset, a match at the cursor itself is good enough.
+    fn search_step(&mut self, search: &mut Search, next: bool) {
+        if search.query().is_empty() {
+            search.failed = false;
+            return;
+        }
+        search.compile();
+        let pattern = match search.pattern() {
+            Ok(pattern) => pattern.clone(),
+            Err(_) => {
+                search.failed = true;
+                return;
+            }
+        };
+        let (cx, cy) = self.cursor();
+        let cur = self.buf().pos_to_char(cx, cy);
+        let from = match (next, search.forward) {
+            (false, _) => cur,
+            (true, true) => cur + 1,
+            (true, false) => match cur.checked_sub(1) {
+                Some(from) => from,
+                None => self.buf().len_chars(),
+            },
+        };
+        match search::find(
+            self.buf(),
+            &pattern,
+            from,
+            search.forward,
+            &mut search.haystack,
+        ) {
+            Some((idx, wrapped)) => {
+                let (x, y) = self.buf().char_to_pos(idx);
+                self.set_cursor(x, y);
+                search.failed = false;
+                search.wrapped |= wrapped;
+            }
+            None => search.failed = true,
+        }
+    }
+
+    /// Handle a key during query-replace, asking about the match at the
+    /// cursor.
+    fn replace_key(&mut self, k: KeyEvent) -> Command {
+        let Replace {
+            re,
+            replacement,
+            found,
+            count,
+            mut haystack,
+        } = self.replace.take().expect("replace");
+        match (k.code, k.modifiers) {
+            (KeyCode::Char('y') | KeyCode::Char(' '), _) => {
+                let end = self.replace_found(&found, &mut haystack);
+                let (at, count) = ((end, Some(end)), count + 1);
+                self.replace_next(re, replacement, at, count, haystack);
+            }
+            (KeyCode::Char('n') | KeyCode::Backspace | KeyCode::Delete, _) => {
+                let at = (found.end, Some(found.end));
+                self.replace_next(re, replacement, at, count, haystack);
+            }
+            (KeyCode::Char('!') | KeyCode::Char('a'), _) => {
+                let mut found = found;
+                let mut count = count;
+                loop {
+                    let end = self.replace_found(&found, &mut haystack);
+                    count += 1;
+                    match search::find_replace(
+                        self.buf(),
+                        &re,
+                        &replacement,
+                        end,
+                        Some(end),
+                        &mut haystack,
+                    ) {
+                        Some(f) => found = f,
+                        None => break,
+                    }
+                }
+                self.replace_done(count);
+            }
+            (KeyCode::Char('q') | KeyCode::Enter | KeyCode::Esc, _)
+            | (KeyCode::Char('g'), KeyModifiers::CONTROL) => {
+                self.replace_done(count)
+            }
+            _ => {
+                self.replace = Some(Replace {
+                    re,
+                    replacement,
+                    found,
+                    count,
+                    haystack,
+                });
+            }
+        }
+        Command::Nothing
+    }
+
+    /// Move to the next match at or after char index from, and ask about
+    /// it; or finish if there are no more matches. prev_end is where the
+    /// previous match ended, if there was one.
+    fn replace_next(
+        &mut self,
+        re: regex::Regex,
+        replacement: String,
+        (from, prev_end): (usize, Option<usize>),
+        count: usize,
+        mut haystack: search::Haystack,
+    ) {
+        match search::find_replace(
+            self.buf(),
+            &re,
+            &replacement,
+            from,
+            prev_end,
+            &mut haystack,
+        ) {
+            Some(found) => {
+                let (x, y) = self.buf().char_to_pos(found.start);
+                self.set_cursor(x, y);
+                let state = Replace {
+                    re,
+                    replacement,
+                    found,
+                    count,
+                    haystack,
+                };
+                self.message = state.prompt();
+                self.replace = Some(state);
+            }
+            None => self.replace_done(count),
+        }
+    }
+
+    /// Replace one match, and return the char index just past the
+    /// replacement. The haystack gets the same change as the buffer.
+    fn replace_found(
+        &mut self,
+        found: &search::Found,
+        haystack: &mut search::Haystack,
+    ) -> usize {
+        let buf = self.buf_mut();
+        haystack.replace(
+            buf.char_to_byte(found.start),
+            buf.char_to_byte(found.end),
+            &found.replacement,
+        );
+        let start = buf.char_to_pos(found.start);
+        let end = buf.char_to_pos(found.end);
+        buf.remove(start, end);
+        let (x, y) = buf.insert(start.0, start.1, &found.replacement);
+        self.set_cursor(x, y);
+        found.start + found.replacement.chars().count()
+    }
+
+    fn replace_done(&mut self, count: usize) {
+        let cursor = self.cursor();
+        self.buf_mut().end_edit(cursor);
+        self.buf_mut().seal();
+        self.message = format!("replaced {} occurrences", count);
+    }
+
+    fn vi_mode(&self) -> Option<Mode> {
+        self.vi.as_ref().map(|vi| vi.mode)
+    }
+
+    fn vi_set_mode(&mut self, mode: Mode) {
+        let cursor = self.cursor();
+        let vi = self.vi.as_mut().expect("vi");
+        if mode == Mode::Visual {
+            vi.anchor = cursor;
+        }
+        vi.mode = mode;
+        if mode != Mode::Insert {
+            self.buf_mut().seal();
+        }
+    }
+
+    /// Keep the cursor on a char in normal and visual mode, as vi does.
+    fn vi_clamp(&mut self) {
+        if matches!(self.vi_mode(), Some(Mode::Normal | Mode::Visual)) {
+            let (x, y) = self.cursor();
+            let len = self.buf().line_len(y);
+            if len > 0 && x >= len {
+                let x = self.buf().prev_grapheme(len, y);
+                self.set_cursor(x, y);
+            }
+        }
+    }
+
+    /// Handle a key in vi normal or visual mode.
+    fn vi_key(&mut self, k: KeyEvent) -> Command {
+        let vi = self.vi.as_mut().expect("vi");
+        match vi.key(k) {
+            vi::Step::Done(action) => self.vi_exec(action),
+            vi::Step::Pass => self.key_command(k),
+            vi::Step::Wait | vi::Step::Invalid => Command::Nothing,
+        }
+    }
+
+    fn vi_exec(&mut self, action: vi::Action) -> Command {
+        match action {
+            vi::Action::Command(_)
+            | vi::Action::Move(Motion::Up | Motion::Down, _) => (),
+            _ => self.goal = None,
+        }
+        match action {
+            vi::Action::Command(cmd) => return cmd,
+            vi::Action::Move(m @ (Motion::Up | Motion::Down), count) => {
+                let (_, y) = vi::target(self.buf(), self.cursor(), m, count);
+                self.move_line(y);
+            }
+            vi::Action::Move(m, count) => {
+                let (x, y) = vi::target(self.buf(), self.cursor(), m, count);
+                self.exec_cmd_move_to(x, y);
+            }
+            vi::Action::Operate(op, m, count) => self.vi_operate(op, m, count),
+            vi::Action::OperateSelection(op) => {
+                if let Some((a, b)) = self.selection(self.focus) {
+                    self.vi_apply(op, a, b);
+                }
+            }
+            vi::Action::Insert(at) => self.vi_insert(at),
+            vi::Action::Paste { before, count } => {
+                self.vi_paste(before, count)
+            }
+            vi::Action::Mode(mode) => self.vi_set_mode(mode),
+            vi::Action::SearchNext { reverse } => self.vi_search_next(reverse),
+        }
+        Command::Nothing
+    }
+
+    /// Make an edit to the current buffer as an undo step of its own. f
+    /// returns where the cursor goes.
+    fn vi_edit<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Buffer) -> (usize, usize),
+    {
+        let cursor = self.cursor();
+        let buf = self.buf_mut();
+        buf.seal();
+        buf.begin_edit(cursor);
+        let (x, y) = f(buf);
+        buf.end_edit((x, y));
+        self.set_cursor(x, y);
+    }
+
+    fn vi_operate(&mut self, op: Operator, m: Motion, count: usize) {
+        let from = self.cursor();
+        let buf = self.buf();
+        // cw on a word changes up to its end, like ce
+        let on_word = from.0 < buf.line_len(from.1)
+            && !buf.char_at(buf.pos_to_char(from.0, from.1)).is_whitespace();
+        let m = match (op, m) {
+            (Operator::Change, Motion::WordForward(big)) if on_word => {
+                Motion::WordEnd(big)
+            }
+            _ => m,
+        };
+        let to = vi::target(buf, from, m, count);
+        if m.linewise() {
+            self.vi_apply_lines(op, from.1, to.1);
+            return;
+        }
+        let (a, mut b) = vi::ordered(from, to);
+        if m.inclusive() && b.0 < buf.line_len(b.1) {
+            b.0 += 1;
+        }
+        // An exclusive motion that ends at the start of a later line stops
+        // at the end of the line before
+        if !m.inclusive() && b.0 == 0 && b.1 > a.1 {
+            b = (buf.line_len(b.1 - 1), b.1 - 1);
+        }
+        self.vi_apply(op, a, b);
+    }
+
+    /// Apply an operator to the text from a up to b.
+    fn vi_apply(
+        &mut self,
+        op: Operator,
+        a: (usize, usize),
+        b: (usize, usize),
+    ) {
+        let text = self.buf().slice(a, b);
+        let vi = self.vi.as_mut().expect("vi");
+        vi.register = text;
+        vi.linewise = false;
+        match op {
+            Operator::Yank => self.set_cursor(a.0, a.1),
+            Operator::Delete | Operator::Change => self.vi_edit(|buf| {
+                buf.remove(a, b);
+                a
+            }),
+        }
+        self.vi_set_mode(match op {
+            Operator::Change => Mode::Insert,
+            _ => Mode::Normal,
+        });
+    }
+
+    /// Apply an operator to lines y0 to y1.
+    fn vi_apply_lines(&mut self, op: Operator, y0: usize, y1: usize) {
+        let (ya, yb) = (y0.min(y1), y0.max(y1));
+        let buf = self.buf();
+        let last = buf.len_lines() - 1;
+        let mut text = buf.slice((0, ya), (buf.line_len(yb), yb));
+        text.push('\n');
+        let vi = self.vi.as_mut().expect("vi");
+        vi.register = text;
+        vi.linewise = true;
+        match op {
+            Operator::Yank => {
+                let (x, _) = self.cursor();
+                self.exec_cmd_move_to(x, ya);
+            }
+            Operator::Delete => self.vi_edit(|buf| {
+                if yb < last {
+                    buf.remove((0, ya), (0, yb + 1));
+                } else if ya > 0 {
+                    let len = buf.line_len(yb);
+                    buf.remove((buf.line_len(ya - 1), ya - 1), (len, yb));
+                } else {
+                    buf.remove((0, 0), (buf.line_len(yb), yb));
+                }
+                let y = ya.min(buf.len_lines() - 1);
+                (buf.first_non_blank(y), y)
+            }),
+            Operator::Change => self.vi_edit(|buf| {
+                buf.remove((0, ya), (buf.line_len(yb), yb));
+                (0, ya)
+            }),
+        }
+        self.vi_set_mode(match op {
+            Operator::Change => Mode::Insert,
+            _ => Mode::Normal,
+        });
+    }
+
+    fn vi_paste(&mut self, before: bool, count: usize) {
+        let vi = self.vi.as_ref().expect("vi");
+        let text = vi.register.repeat(count.max(1));
+        let linewise = vi.linewise;
+        if text.is_empty() {
+            self.message = "nothing to paste".into();
+            return;
+        }
+        let (x, y) = self.cursor();
+        self.vi_edit(|buf| {
+            if linewise {
+                let y = if before { y } else { y + 1 };
+                if y < buf.len_lines() {
+                    buf.insert(0, y, &text);
+                } else {
+                    // Below the last line, which may not end in a newline
+                    let last = buf.len_lines() - 1;
+                    let text = text.strip_suffix('\n').unwrap_or(&text);
+                    buf.insert(
+                        buf.line_len(last),
+                        last,
+                        &format!("\n{}", text),
+                    );
+                }
+                (buf.first_non_blank(y), y)
+            } else {
+                let x = if before { x } else { buf.next_grapheme(x, y) };
+                let (ex, ey) = buf.insert(x, y, &text);
+                // The cursor ends up on the last grapheme pasted
+                if ex > 0 {
+                    (buf.prev_grapheme(ex, ey), ey)
+                } else {
+                    buf.char_to_pos(buf.pos_to_char(ex, ey) - 1)
+                }
+            }
+        });
+    }
+
+    fn vi_insert(&mut self, at: InsertAt) {
+        let (x, y) = self.cursor();
+        let len = self.buf().line_len(y);
+        match at {
+            InsertAt::Cursor => (),
+            InsertAt::After => {
+                self.set_cursor(self.buf().next_grapheme(x, y), y)
+            }
+            InsertAt::LineStart => {
+                self.set_cursor(self.buf().first_non_blank(y), y)
+            }
+            InsertAt::LineEnd => self.set_cursor(len, y),
+            InsertAt::LineBelow => {
+                self.vi_edit(|buf| buf.insert(len, y, "\n"))
+            }
+            InsertAt::LineAbove => self.vi_edit(|buf| {
+                buf.insert(0, y, "\n");
+                (0, y)
+            }),
+        }
+        self.vi_set_mode(Mode::Insert);
+    }
+
+    /// Move to the next match of the last search, in the direction it was
+    /// made in, or the other one.
+    fn vi_search_next(&mut self, reverse: bool) {
+        let forward = self.vi.as_ref().expect("vi").search_forward != reverse;
+        let query = match self.history(PromptKind::Search).last() {
+            Some(query) => query.clone(),
+            None => {
+                self.message = "no previous search".into();
+                return;
+            }
+        };
+        let pattern = Pattern::Plain(query.clone());
+        let (cx, cy) = self.cursor();
+        let cur = self.buf().pos_to_char(cx, cy);
+        let from = match (forward, cur.checked_sub(1)) {
+            (true, _) => cur + 1,
+            (false, Some(from)) => from,
+            (false, None) => self.buf().len_chars(),
+        };
+        // A plain pattern is never run on the whole buffer
+        let mut haystack = search::Haystack::default();
+        let found =
+            search::find(self.buf(), &pattern, from, forward, &mut haystack);
+        match found {
+            Some((idx, wrapped)) => {
+                let (x, y) = self.buf().char_to_pos(idx);
+                self.set_cursor(x, y);
+                if wrapped {
+                    self.message = "search wrapped".into();
+                }
+            }
+            None => self.message = format!("not found: {}", query),
+        }
+    }
+
+    /// Run a command typed in by name, as read by `commands::parse`, or
+    /// one of the vi shorthands: w, q, q!, wq, x, e FILE, w FILE, or a
+    /// line number.
+    fn execute(&mut self, text: &str) -> Command {
+        let text = text.trim();
+        match text {
+            "w" => return Command::Save,
+            "q" => return Command::Exit,
+            "q!" => {
+                self.confirm = Some("exit");
+                return Command::Exit;
+            }
+            "wq" | "x" => {
+                return match self.buf_mut().save() {
+                    Ok(()) => Command::Exit,
+                    Err(err) => {
+                        self.message = format!("cannot save: {}", err);
+                        Command::Nothing
+                    }
+                }
+            }
+            _ => (),
+        }
+        if let Some(file) = text.strip_prefix("e ") {
+            return Command::Open(file.trim().into());
+        }
+        if let Some(file) = text.strip_prefix("w ") {
+            return Command::SaveAs(file.trim().into());
+        }
+        if let Ok(n) = text.parse() {
+            return Command::GotoLine(n);
+        }
+        match commands::parse(text) {
+            Some(cmd) => cmd,
+            None => {
+                self.message = format!("unknown command: {}", text);
+                Command::Nothing
+            }
+        }
     }
 
     fn exec_cmd(&mut self, cmd: Command) -> Result<Option<Exit>> {
+        let confirm = match cmd {
+            Command::Nothing => self.confirm,
+            _ => self.confirm.take(),
+        };
+        // Consecutive insertions of word characters are undone together
+        match cmd {
+            Command::Nothing => (),
+            Command::InsertCharacter(ch) if !ch.is_whitespace() => (),
+            _ => self.buf_mut().seal(),
+        }
+        match cmd {
+            Command::Nothing
+            | Command::Move(Direction::Up | Direction::Down)
+            | Command::Select(Direction::Up | Direction::Down)
+            | Command::MovePageUp
+            | Command::MovePageDown => (),
+            _ => self.goal = None,
+        }
+        // Plain movement ends a selection made with shift, and edits other
+        // than those working on the region drop it
+        match cmd {
+            Command::Move(_)
+            | Command::MoveTo(..)
+            | Command::Click(..)
+            | Command::MovePageUp
+            | Command::MovePageDown
+            | Command::MoveLineHome
+            | Command::MoveLineEnd
+            | Command::MoveBufferStart
+            | Command::MoveBufferEnd
+            | Command::MoveWord(_)
+            | Command::MoveParagraph(_)
+            | Command::MoveFirstNonBlank
+            | Command::GotoLine(_)
+                if self.win().selecting =>
+            {
+                self.win_mut().mark = None
+            }
+            Command::KillLine
+            | Command::KillWord(_)
+            | Command::Yank
+            | Command::YankPop
+            | Command::Undo
+            | Command::Redo
+            | Command::NextBuffer
+            | Command::PrevBuffer
+            | Command::SwitchBuffer(_)
+            | Command::Open(_)
+            | Command::CloseBuffer => self.win_mut().mark = None,
+            _ => (),
+        }
         match cmd {
             Command::Nothing => (),
             Command::Panic(s) => panic!("{}", s),
             Command::Exit => {
+                let unsaved = self.unsaved();
+                if !unsaved.is_empty() && confirm != Some("exit") {
+                    self.message = format!(
+                        "unsaved changes in {}; exit again to discard them",
+                        unsaved.join(", ")
+                    );
+                    self.confirm = Some("exit");
+                    return Ok(None);
+                }
                 self.update_screen()?;
                 return Ok(Some(Exit));
             }
+            Command::Save if self.buf().path.is_none() => {
+                self.ask(PromptKind::File, "Save as: ", Action::SaveAs);
+            }
+            Command::Save => match self.buf_mut().save() {
+                Ok(()) => {
+                    self.message = format!("saved {}", self.buf().name);
+                }
+                Err(err) => {
+                    self.message = format!("cannot save: {}", err);
+                }
+            },
+            Command::SaveAs(file) => {
+                let path = prompt::expand_path(&file);
+                let name = match path.file_name() {
+                    Some(name) => name.to_string_lossy().into_owned(),
+                    None => {
+                        self.message = format!("not a file name: {}", file);
+                        return Ok(None);
+                    }
+                };
+                // The buffer's own name should not count as taken
+                self.buf_mut().name.clear();
+                let name = self.unique_name(&name);
+                let buf = self.buf_mut();
+                buf.name = name;
+                if let Err(err) = buf.set_path(&path) {
+                    self.message = format!("cannot save: {}", err);
+                    return Ok(None);
+                }
+                return self.exec_cmd(Command::Save);
+            }
+            Command::Open(file) => {
+                if let Err(err) = self.open(&prompt::expand_path(&file)) {
+                    self.message = format!("cannot open {}: {}", file, err);
+                }
+            }
+            Command::NextBuffer => {
+                self.switch_to((self.win().buf + 1) % self.buffers.len());
+            }
+            Command::PrevBuffer => {
+                let n = self.buffers.len();
+                self.switch_to((self.win().buf + n - 1) % n);
+            }
+            Command::SwitchBuffer(name) => {
+                match self.buffers.iter().position(|b| b.name == name) {
+                    Some(idx) => self.switch_to(idx),
+                    None => self.message = format!("no buffer: {}", name),
+                }
+            }
+            Command::CloseBuffer => {
+                let buf = self.buf();
+                if buf.modified
+                    && buf.path.is_some()
+                    && confirm != Some("close")
+                {
+                    self.message = format!(
+                        "{} has unsaved changes; close again to discard them",
+                        buf.name
+                    );
+                    self.confirm = Some("close");
+                    return Ok(None);
+                }
+                self.close_buffer();
+            }
+            Command::ListBuffers => {
+                let names: Vec<String> = self
+                    .buffers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| {
+                        let flag = if b.modified { "*" } else { "" };
+                        if i == self.win().buf {
+                            format!("[{}{}]", flag, b.name)
+                        } else {
+                            format!("{}{}", flag, b.name)
+                        }
+                    })
+                    .collect();
+                self.message = names.join(" ");
+            }
+            Command::SplitHorizontal => self.split_window(Split::Horizontal),
+            Command::SplitVertical => self.split_window(Split::Vertical),
+            Command::CloseWindow => self.close_window(self.focus),
+            Command::CloseOtherWindows => {
+                self.windows = vec![self.win().clone()];
+                self.layout = Layout::Window(0);
+                self.focus = 0;
+            }
+            Command::NextWindow => {
+                let order: Vec<usize> = self
+                    .layout
+                    .rects(self.screen_area())
+                    .into_iter()
+                    .map(|(w, _)| w)
+                    .collect();
+                let i = order.iter().position(|w| *w == self.focus);
+                self.focus = order[(i.unwrap_or(0) + 1) % order.len()];
+            }
+            Command::FocusWindow(d) => {
+                let rects = self.layout.rects(self.screen_area());
+                let r = self.win_rect(self.focus);
+                let win = self.win();
+                let at = (
+                    r.x + win.cx.saturating_sub(win.ox),
+                    r.y + win.cy.saturating_sub(win.oy),
+                );
+                match window::neighbour(&rects, self.focus, d, at) {
+                    Some(w) => self.focus = w,
+                    None => self.message = "no window there".into(),
+                }
+            }
+            Command::GrowWindow | Command::ShrinkWindow => {
+                let delta = match cmd {
+                    Command::GrowWindow => 1,
+                    _ => -1,
+                };
+                let area = self.screen_area();
+                if !self.layout.resize(self.focus, delta, area) {
+                    self.message = "cannot resize the only window".into();
+                }
+            }
+            Command::SearchForward
+            | Command::SearchBackward
+            | Command::SearchRegexForward
+            | Command::SearchRegexBackward => {
+                let forward = matches!(
+                    cmd,
+                    Command::SearchForward | Command::SearchRegexForward
+                );
+                let regex = matches!(
+                    cmd,
+                    Command::SearchRegexForward | Command::SearchRegexBackward
+                );
+                self.search =
+                    Some(Search::new(forward, regex, self.win().clone()));
+            }
+            Command::QueryReplace => self.ask(
+                PromptKind::Replace,
+                "Query replace regexp: ",
+                Action::ReplacePattern,
+            ),
+            Command::ChooseLine => {
+                self.ask(PromptKind::Line, "Go to line: ", Action::GotoLine)
+            }
+            Command::ChooseFile => {
+                self.ask(PromptKind::File, "Find file: ", Action::Open)
+            }
+            Command::ChooseSaveAs => {
+                self.ask(PromptKind::File, "Save as: ", Action::SaveAs)
+            }
+            Command::ChooseBuffer => self.ask(
+                PromptKind::Buffer,
+                "Switch to buffer: ",
+                Action::SwitchBuffer,
+            ),
+            Command::Execute => {
+                self.ask(PromptKind::Command, ":", Action::Execute)
+            }
+            Command::ToggleWhitespace => {
+                self.show_whitespace = !self.show_whitespace;
+                self.message = if self.show_whitespace {
+                    "showing whitespace".into()
+                } else {
+                    "hiding whitespace".into()
+                };
+            }
+            Command::LineNumbers(mode) => {
+                self.line_numbers = mode;
+            }
+            Command::DescribeKey => {
+                self.describe = true;
+                self.message = "describe key: ".into();
+            }
+            Command::GotoLine(n) => {
+                self.exec_cmd_move_to(0, n.saturating_sub(1));
+            }
+            Command::Undo => match self.buf_mut().undo() {
+                Some((x, y)) => {
+                    self.exec_cmd_move_to(x, y);
+                }
+                None => self.message = "nothing to undo".into(),
+            },
+            Command::Redo => match self.buf_mut().redo() {
+                Some((x, y)) => {
+                    self.exec_cmd_move_to(x, y);
+                }
+                None => self.message = "nothing to redo".into(),
+            },
             Command::InsertCharacter(ch) => {
+                let cursor = self.cursor();
+                self.buf_mut().begin_edit(cursor);
+                // Typed text replaces the region
+                self.remove_region();
                 self.exec_cmd_insert(ch);
+                let cursor = self.cursor();
+                self.buf_mut().end_edit(cursor);
+            }
+            Command::InsertText(text) => {
+                let cursor = self.cursor();
+                self.buf_mut().begin_edit(cursor);
+                self.remove_region();
+                let (cx, cy) = self.cursor();
+                let (x, y) = self.buf_mut().insert(cx, cy, &text);
+                self.set_cursor(x, y);
+                let buf = self.buf_mut();
+                buf.end_edit((x, y));
+                // Typing after a paste is undone separately
+                buf.seal();
+            }
+            Command::InsertTab if self.selection(self.focus).is_some() => {
+                self.indent_region(true);
+            }
+            Command::InsertTab => {
+                let cursor = self.cursor();
+                let tab = if self.expand_tab {
+                    let col = self.cursor_col(self.focus);
+                    " ".repeat(self.tab_width - col % self.tab_width)
+                } else {
+                    String::from("\t")
+                };
+                let buf = self.buf_mut();
+                buf.begin_edit(cursor);
+                let (x, y) = buf.insert(cursor.0, cursor.1, &tab);
+                buf.end_edit((x, y));
+                self.set_cursor(x, y);
             }
             Command::Move(d) => {
                 self.exec_cmd_move(d);
@@ -197,184 +1812,694 @@ impl Editor {
             Command::MoveTo(x, y) => {
                 self.exec_cmd_move_to(x, y);
             }
+            Command::Click(x, y) => {
+                let cmd = self.focus_at(x, y);
+                return self.exec_cmd(cmd);
+            }
             Command::MovePageUp => {
-                self.cx = 0;
-                self.cy = max(0, self.cy - self.term.wy);
+                let page = self.win_rect(self.focus).h.saturating_sub(1);
+                let (_, cy) = self.cursor();
+                self.move_line(cy.saturating_sub(page));
             }
             Command::MovePageDown => {
-                self.cx = 0;
-                self.cy = min(self.lines.len() - 1, self.cy + self.term.wy);
+                let page = self.win_rect(self.focus).h.saturating_sub(1);
+                let (_, cy) = self.cursor();
+                self.move_line(cy + page);
             }
             Command::MoveLineHome => {
-                self.cx = 0;
+                self.win_mut().cx = 0;
             }
             Command::MoveLineEnd => {
-                self.cx = self.lines[self.cy].ulen() - 1;
+                self.win_mut().cx = self.buf().line_len(self.win().cy);
+            }
+            Command::MoveBufferStart => self.set_cursor(0, 0),
+            Command::MoveWord(d) => {
+                let (cx, cy) = self.cursor();
+                let idx = self.buf().pos_to_char(cx, cy);
+                let idx = match d {
+                    Direction::Left => self.buf().word_start(idx),
+                    Direction::Right => self.buf().word_end(idx),
+                    _ => idx,
+                };
+                let (x, y) = self.buf().char_to_pos(idx);
+                self.set_cursor(x, y);
+            }
+            Command::MoveParagraph(d) => {
+                let (_, cy) = self.cursor();
+                let last = self.buf().len_lines() - 1;
+                match d {
+                    Direction::Up => {
+                        self.set_cursor(0, self.buf().paragraph_start(cy))
+                    }
+                    Direction::Down => match self.buf().paragraph_end(cy) {
+                        y if y == last => {
+                            self.set_cursor(self.buf().line_len(y), y)
+                        }
+                        y => self.set_cursor(0, y),
+                    },
+                    _ => (),
+                }
+            }
+            Command::MoveFirstNonBlank => {
+                let (_, cy) = self.cursor();
+                self.set_cursor(self.buf().first_non_blank(cy), cy);
+            }
+            Command::MoveBufferEnd => {
+                let y = self.buf().len_lines() - 1;
+                self.set_cursor(self.buf().line_len(y), y);
+            }
+            Command::KillLine => {
+                let (cx, cy) = self.cursor();
+                let end = if cx < self.buf().line_len(cy) {
+                    (self.buf().line_len(cy), cy)
+                } else if cy + 1 < self.buf().len_lines() {
+                    (0, cy + 1)
+                } else {
+                    return Ok(None);
+                };
+                self.kill((cx, cy), end, false);
+            }
+            Command::KillWord(d) => {
+                let (cx, cy) = self.cursor();
+                let idx = self.buf().pos_to_char(cx, cy);
+                let (to, before) = match d {
+                    Direction::Left => (self.buf().word_start(idx), true),
+                    Direction::Right => (self.buf().word_end(idx), false),
+                    _ => return Ok(None),
+                };
+                let to = self.buf().char_to_pos(to);
+                self.kill((cx, cy), to, before);
+            }
+            Command::Yank => {
+                self.paste_clipboard();
+                let text = match self.kills.yank() {
+                    Some(text) => text.to_string(),
+                    None => {
+                        self.message = "kill ring is empty".into();
+                        return Ok(None);
+                    }
+                };
+                let (cx, cy) = self.cursor();
+                let buf = self.buf_mut();
+                buf.begin_edit((cx, cy));
+                let (x, y) = buf.insert(cx, cy, &text);
+                buf.end_edit((x, y));
+                self.set_cursor(x, y);
+                self.yanked = Some(((cx, cy), (x, y)));
+            }
+            Command::YankPop => {
+                let (from, to) = match (&self.last_cmd, self.yanked) {
+                    (Command::Yank | Command::YankPop, Some(range)) => range,
+                    _ => {
+                        self.message =
+                            "previous command was not a yank".into();
+                        return Ok(None);
+                    }
+                };
+                let text = match self.kills.yank_pop() {
+                    Some(text) => text.to_string(),
+                    None => return Ok(None),
+                };
+                let buf = self.buf_mut();
+                buf.begin_edit(to);
+                buf.remove(from, to);
+                let (x, y) = buf.insert(from.0, from.1, &text);
+                buf.end_edit((x, y));
+                self.set_cursor(x, y);
+                self.yanked = Some((from, (x, y)));
+            }
+            Command::SetMark => {
+                let cursor = self.cursor();
+                let win = self.win_mut();
+                win.mark = Some(cursor);
+                win.selecting = false;
+                self.message = "mark set".into();
+            }
+            Command::Select(d) => {
+                let cursor = self.cursor();
+                let win = self.win_mut();
+                if win.mark.is_none() {
+                    win.mark = Some(cursor);
+                    win.selecting = true;
+                }
+                self.exec_cmd_move(d);
+            }
+            Command::Cancel => {
+                self.win_mut().mark = None;
+                self.message = "quit".into();
+            }
+            Command::DeleteRegion
+            | Command::CopyRegion
+            | Command::CutRegion
+            | Command::IndentRegion
+            | Command::DedentRegion
+                if self.selection(self.focus).is_none() =>
+            {
+                self.message = "no region".into();
+            }
+            Command::DeleteRegion => {
+                let cursor = self.cursor();
+                self.buf_mut().begin_edit(cursor);
+                self.remove_region();
+                let cursor = self.cursor();
+                self.buf_mut().end_edit(cursor);
+            }
+            Command::CopyRegion => {
+                if let Some((a, b)) = self.selection(self.focus) {
+                    let text = self.buf().slice(a, b);
+                    self.save_kill(text, false);
+                    self.win_mut().mark = None;
+                }
+            }
+            Command::CutRegion => {
+                let cursor = self.cursor();
+                self.buf_mut().begin_edit(cursor);
+                if let Some(text) = self.remove_region() {
+                    self.save_kill(text, false);
+                }
+                let cursor = self.cursor();
+                self.buf_mut().end_edit(cursor);
+            }
+            Command::IndentRegion => self.indent_region(true),
+            Command::DedentRegion => self.indent_region(false),
+            Command::EraseWord(d) => {
+                let (cx, cy) = self.cursor();
+                let idx = self.buf().pos_to_char(cx, cy);
+                let to = match d {
+                    Direction::Left => self.buf().word_start(idx),
+                    Direction::Right => self.buf().word_end(idx),
+                    _ => return Ok(None),
+                };
+                let to = self.buf().char_to_pos(to);
+                let (a, _) = vi::ordered((cx, cy), to);
+                let buf = self.buf_mut();
+                buf.begin_edit((cx, cy));
+                buf.remove((cx, cy), to);
+                buf.end_edit(a);
+                self.set_cursor(a.0, a.1);
             }
             Command::Erase(d) => {
-                self.exec_cmd_erase(d);
+                let cursor = self.cursor();
+                self.buf_mut().begin_edit(cursor);
+                if self.remove_region().is_none() {
+                    self.exec_cmd_erase(d);
+                }
+                let cursor = self.cursor();
+                self.buf_mut().end_edit(cursor);
             }
         }
-        return Ok(None);
+        self.clamp_cursors();
+        Ok(None)
     }
 
     fn exec_cmd_move(&mut self, d: Direction) {
+        let (cx, cy) = self.cursor();
         match d {
             Direction::Left => {
-                if self.cx > 0 {
-                    self.cx -= 1
-                }
+                let x = self.buf().prev_grapheme(cx, cy);
+                self.set_cursor(x, cy);
             }
-            Direction::Right => self.cx += 1,
-            Direction::Up => {
-                if self.cy > 0 {
-                    self.cy -= 1
-                }
+            Direction::Right => {
+                let x = self.buf().next_grapheme(cx, cy);
+                self.set_cursor(x, cy);
             }
-            Direction::Down => self.cy += 1,
+            Direction::Up => self.move_line(cy.saturating_sub(1)),
+            Direction::Down => self.move_line(cy + 1),
         }
-        self.cy = min(self.cy, self.lines.len() - 1);
-        self.cx = min(self.cx, self.lines[self.cy].ulen());
     }
 
+    /// Move to line y, keeping to the goal column: the display column the
+    /// cursor was in when vertical movement started.
+    fn move_line(&mut self, y: usize) {
+        let col = match self.goal {
+            Some(col) => col,
+            None => self.cursor_col(self.focus),
+        };
+        self.goal = Some(col);
+        let y = min(y, self.buf().len_lines() - 1);
+        let line = Editor::line_to_col(self.buf(), y, col, self.tab_width);
+        let x = display::idx(&line, col, self.tab_width);
+        self.set_cursor(x, y);
+    }
+
+    /// Move to x, y clamped to the buffer, and to the start of the grapheme
+    /// cluster x falls in.
     fn exec_cmd_move_to(&mut self, x: usize, y: usize) {
-        self.cy = max(0, min(y, self.lines.len() - 1));
-        self.cx = max(0, min(x, self.lines[self.cy].ulen()));
+        let cy = min(y, self.buf().len_lines() - 1);
+        let cx = self.buf().grapheme_floor(x, cy);
+        self.set_cursor(cx, cy);
     }
 
-    fn exec_cmd_insert(&mut self, ch: char) {
-        match ch {
-            '\n' => {
-                // Take the current line, and break it in two
-                let line = self.lines[self.cy].clone();
-                self.lines[self.cy] = line.uslice(0, self.cx);
-                self.cy += 1;
-                self.lines
-                    .insert(self.cy, line.uslice(self.cx, line.ulen()));
-                self.cx = 0;
+    /// Remove the text between two positions and save it in the kill ring.
+    /// before says whether it was killed backwards from the cursor.
+    fn kill(
+        &mut self,
+        from: (usize, usize),
+        to: (usize, usize),
+        before: bool,
+    ) {
+        let cursor = self.cursor();
+        let (a, _) = vi::ordered(from, to);
+        let buf = self.buf_mut();
+        buf.begin_edit(cursor);
+        let text = buf.remove(from, to);
+        buf.end_edit(a);
+        self.set_cursor(a.0, a.1);
+        self.save_kill(text, before);
+    }
+
+    /// Save killed text in the kill ring, and copy it to the clipboard.
+    /// Kills in a row make up a single entry.
+    fn save_kill(&mut self, text: String, before: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.last_cmd {
+            Command::KillLine | Command::KillWord(_) | Command::CutRegion => {
+                self.kills.append(&text, before)
             }
-            _ => {
-                // Insert in the middle of the current line
-                let line = &self.lines[self.cy];
-                let len = line.ulen();
-                let mut newline = String::with_capacity(len + 1);
-                newline.push_str(&line.uslice(0, self.cx));
-                newline.push(ch);
-                newline.push_str(&line.uslice(self.cx, len));
-                self.lines[self.cy] = newline;
-                self.exec_cmd_move(Direction::Right);
+            _ => self.kills.push(text),
+        }
+        let latest = self.kills.latest().unwrap_or_default();
+        if let Err(err) = self.clipboard.copy(latest, &mut self.term.stdout) {
+            self.message = format!("cannot copy to clipboard: {}", err);
+        }
+    }
+
+    /// Add the text on the clipboard to the kill ring, unless it's there
+    /// already, so that text copied elsewhere can be yanked.
+    fn paste_clipboard(&mut self) {
+        match self.clipboard.paste() {
+            Some(Ok(text))
+                if !text.is_empty() && self.kills.latest() != Some(&text) =>
+            {
+                self.kills.push(text)
+            }
+            Some(Err(err)) => {
+                self.message = format!("cannot paste from clipboard: {}", err)
             }
+            _ => (),
+        }
+    }
+
+    /// Remove the text in the region, if there is one, and return it.
+    fn remove_region(&mut self) -> Option<String> {
+        let (a, b) = self.selection(self.focus)?;
+        self.win_mut().mark = None;
+        if let Some(vi) = self.vi.as_mut().filter(|vi| vi.mode == Mode::Visual)
+        {
+            vi.mode = Mode::Normal;
+        }
+        if a == b {
+            return None;
         }
+        let text = self.buf_mut().remove(a, b);
+        self.set_cursor(a.0, a.1);
+        Some(text)
+    }
+
+    /// Indent the lines of the region by one level, or dedent them. The
+    /// region is widened to the whole lines.
+    fn indent_region(&mut self, indent: bool) {
+        let ((_, y0), (x1, y1)) = match self.selection(self.focus) {
+            Some(region) => region,
+            None => return,
+        };
+        // A region ending at the start of a line doesn't include it
+        let y1 = if x1 == 0 && y1 > y0 { y1 - 1 } else { y1 };
+        let cursor = self.cursor();
+        let width = self.tab_width;
+        let indent_by = if self.expand_tab {
+            " ".repeat(width)
+        } else {
+            String::from("\t")
+        };
+        let buf = self.buf_mut();
+        buf.begin_edit(cursor);
+        for y in y0..=y1 {
+            let line = buf.line(y);
+            if indent && !line.is_empty() {
+                buf.insert(0, y, &indent_by);
+            } else if !indent {
+                let n = if line.starts_with('\t') {
+                    1
+                } else {
+                    line.chars().take(width).take_while(|c| *c == ' ').count()
+                };
+                buf.remove((0, y), (n, y));
+            }
+        }
+        let end = (buf.line_len(y1), y1);
+        buf.end_edit(end);
+        self.win_mut().mark = Some((0, y0));
+        self.set_cursor(end.0, end.1);
+    }
+
+    fn exec_cmd_insert(&mut self, ch: char) {
+        let mut tmp = [0; 4];
+        let (cx, cy) = self.cursor();
+        let (x, y) = self.buf_mut().insert(cx, cy, ch.encode_utf8(&mut tmp));
+        self.set_cursor(x, y);
     }
 
     fn exec_cmd_erase(&mut self, d: Direction) {
+        let (cx, cy) = self.cursor();
         match d {
             Direction::Left => {
-                if self.cx == 0 {
-                    if self.cy == 0 {
+                if cx == 0 {
+                    if cy == 0 {
                         // top left, do nothing
                         return;
                     }
                     // join this line with previous
-                    let line = self.lines.remove(self.cy);
-                    self.cy -= 1;
-                    self.cx = self.lines[self.cy].ulen();
-                    let mut newline =
-                        String::with_capacity(self.cx + line.ulen());
-                    newline.push_str(&self.lines[self.cy]);
-                    newline.push_str(&line);
-                    self.lines[self.cy] = newline;
+                    let x = self.buf().line_len(cy - 1);
+                    self.buf_mut().remove((x, cy - 1), (0, cy));
+                    self.set_cursor(x, cy - 1);
                 } else {
-                    // remove from the middle
-                    let line = &self.lines[self.cy];
-                    let len = line.ulen();
-                    let mut newline = String::with_capacity(len - 1);
-                    newline.push_str(&line.uslice(0, self.cx - 1));
-                    newline.push_str(&line.uslice(self.cx, len));
-                    self.lines[self.cy] = newline;
-                    self.exec_cmd_move(Direction::Left);
+                    // remove the grapheme cluster before the cursor
+                    let x = self.buf().prev_grapheme(cx, cy);
+                    self.buf_mut().remove((x, cy), (cx, cy));
+                    self.set_cursor(x, cy);
                 }
             }
             Direction::Right => {
-                // todo
+                if cx < self.buf().line_len(cy) {
+                    let x = self.buf().next_grapheme(cx, cy);
+                    self.buf_mut().remove((cx, cy), (x, cy));
+                } else if cy + 1 < self.buf().len_lines() {
+                    // join the next line with this one
+                    self.buf_mut().remove((cx, cy), (0, cy + 1));
+                }
             }
             _ => {} // noop
         }
     }
 
-    fn scroll_to_cursor(&mut self) {
-        self.ox = 0;
-        while (self.cx - self.ox) >= (self.term.wx as f32 * 0.90) as usize {
-            self.ox += (self.term.wx as f32 * 0.85) as usize;
+    /// Scroll the focused window sideways so that its cursor, at display
+    /// column col, is in view.
+    fn scroll_to_cursor(&mut self, col: usize) {
+        let rect = self.text_rect(self.focus, self.win_rect(self.focus));
+        let wx = rect.w.saturating_sub(1);
+        // Scroll by most of a window at a time, but by at least a column so
+        // that this ends in very narrow windows; the step is never bigger
+        // than the edge, so it doesn't go past the cursor
+        let edge = ((wx as f32 * 0.90) as usize).max(1);
+        let step = ((wx as f32 * 0.85) as usize).max(1);
+        let win = self.win_mut();
+        win.ox = 0;
+        while (col - win.ox) >= edge {
+            win.ox += step;
         }
     }
 
-    fn update_screen(&mut self) -> Result<()> {
-        if self.cy < self.oy {
-            self.oy = self.cy;
+    /// The screen column window w's cursor is at, counting from the start
+    /// of the line rather than the window's left edge.
+    fn cursor_col(&self, w: usize) -> usize {
+        let win = &self.windows[w];
+        let line = self.buffers[win.buf].line_slice(win.cy, 0, win.cx);
+        display::col(&line, win.cx, self.tab_width)
+    }
+
+    /// The start of line y, up to and including the glyph at display
+    /// column col, or the whole line if it's narrower than that. Only this
+    /// much is copied, so that long lines stay cheap to lay out.
+    fn line_to_col(
+        buf: &Buffer,
+        y: usize,
+        col: usize,
+        tab_width: usize,
+    ) -> String {
+        // Every glyph takes at least a char and a cell
+        let mut n = col + 2;
+        loop {
+            let part = buf.line_slice(y, 0, n);
+            let len = part.ulen();
+            if len < n {
+                return part;
+            }
+            // The last glyph may be cut off, so take more unless it's past
+            // the one at col
+            let past = display::glyphs(&part, tab_width, usize::MAX)
+                .skip_while(|g| g.col + g.width <= col)
+                .nth(1)
+                .is_some();
+            if past {
+                return part;
+            }
+            n *= 2;
+        }
+    }
+
+    /// Scroll window w so that its cursor, at display column col, is
+    /// within its text area.
+    fn scroll_window(&mut self, w: usize, rect: Rect, col: usize) {
+        let rect = self.text_rect(w, rect);
+        let (wx, wy) = (rect.w.max(1), rect.h.max(1));
+        let win = &mut self.windows[w];
+        if win.cy < win.oy {
+            win.oy = win.cy;
         }
-        if self.cx < self.ox {
-            self.ox = self.cx;
+        if col < win.ox {
+            win.ox = col;
         }
-        if self.cy >= self.oy + self.term.wy {
-            self.oy = self.cy - self.term.wy + 1;
+        if win.cy >= win.oy + wy {
+            win.oy = win.cy + 1 - wy;
         }
-        if self.cx >= self.ox + self.term.wx {
-            self.ox = self.cx - self.term.wx + 1;
+        if col >= win.ox + wx {
+            win.ox = col + 1 - wx;
         }
+    }
+
+    fn update_screen(&mut self) -> Result<()> {
         if self.term.wy == 0 || self.term.wx == 0 {
             return Ok(());
         }
+        let area = self.screen_area();
+        let rects = self.layout.rects(area);
+
+        self.screen.resize(area.w, area.h);
+        // Finding the cursor's column lays out the line up to it, so it's
+        // done once per window and frame
+        let mut focus_col = None;
+        for (w, rect) in rects.iter() {
+            let col = self.cursor_col(*w);
+            if *w == self.focus {
+                self.scroll_to_cursor(col);
+                focus_col = Some(col);
+            }
+            self.scroll_window(*w, *rect, col);
+            self.draw_window(*w, *rect);
+        }
+        for sep in self.layout.separators(area) {
+            for y in sep.y..sep.y + sep.h {
+                self.screen.put(sep.x, y, "|", 1, Face::Decoration);
+            }
+        }
+
+        let rect = self.win_rect(self.focus);
+        let text = self.text_rect(self.focus, rect);
+        let col = focus_col.unwrap_or_else(|| self.cursor_col(self.focus));
+        let win = self.win();
+        let (x, y) = match self.active_prompt() {
+            Some(prompt) => {
+                (rect.x + prompt.render(rect.w).1, rect.y + rect.h - 1)
+            }
+            None => (text.x + col - win.ox, text.y + win.cy - win.oy),
+        };
+        self.screen.flush(&mut self.term.stdout, (x, y))
+    }
+
+    /// The selected part of window w's buffer, from the first selected
+    /// position up to (not including) the second: the region between the
+    /// mark and the cursor, or the vi visual selection.
+    fn selection(&self, w: usize) -> Option<((usize, usize), (usize, usize))> {
+        if w != self.focus {
+            return None;
+        }
+        let win = &self.windows[w];
+        let buf = &self.buffers[win.buf];
+        let cursor = (win.cx, win.cy);
+        match &self.vi {
+            Some(vi) if vi.mode == Mode::Visual => {
+                let (a, b) = vi::ordered(vi.anchor, cursor);
+                // The char under the cursor is part of the selection
+                let end = (buf.pos_to_char(b.0, b.1) + 1).min(buf.len_chars());
+                Some((a, buf.char_to_pos(end)))
+            }
+            _ => {
+                // The mark may be out of date if the buffer changed in
+                // another window
+                let (x, y) = win.mark?;
+                let mark = buf.char_to_pos(buf.pos_to_char(x, y));
+                Some(vi::ordered(mark, cursor))
+            }
+        }
+    }
+
+    /// The prompt being shown in the focused window's status line, if any.
+    fn active_prompt(&self) -> Option<&Prompt> {
+        match (&self.prompt, &self.search) {
+            (Some((prompt, _)), _) => Some(prompt),
+            (None, Some(search)) => Some(&search.prompt),
+            (None, None) => None,
+        }
+    }
+
+    fn draw_window(&mut self, w: usize, rect: Rect) {
+        if rect.h == 0 || rect.w == 0 {
+            return;
+        }
+        let prompt = match self.active_prompt() {
+            Some(prompt) if w == self.focus => Some(prompt.render(rect.w).0),
+            _ => None,
+        };
+        let selection = self.selection(w);
+        let text = self.text_rect(w, rect);
+        let gutter = text.x - rect.x;
+        let win = &self.windows[w];
+        let buf = &self.buffers[win.buf];
+        let screen = &mut self.screen;
+        let height = rect.h - 1;
+        let pattern = match (&self.search, &self.replace) {
+            _ if w != self.focus => None,
+            (Some(search), _) if !search.query().is_empty() => {
+                search.pattern().ok().cloned()
+            }
+            (_, Some(replace)) => Some(Pattern::Regex(replace.re.clone())),
+            _ => None,
+        };
+
+        for row in 0..height {
+            let y = win.oy + row;
+            let sy = rect.y + row;
+            if y < buf.len_lines() && gutter > 0 {
+                let n = self.line_numbers.number(y, win.cy);
+                let label = format!("{:>1$} ", n, gutter - 1);
+                screen.print(rect.x, sy, &label, gutter, Face::Gutter);
+            } else {
+                screen.fill(rect.x, sy, gutter, Face::Normal);
+            }
+            if y < buf.len_lines() {
+                let (left, right) = (win.ox, win.ox + text.w);
+                // Lay out only as far as the window shows
+                let line = Editor::line_to_col(buf, y, right, self.tab_width);
+                // Highlight search matches, the one at the cursor in a
+                // different colour, and the selection. A plain query is
+                // looked for only in the columns shown, give or take its
+                // length; a regex can depend on the whole line
+                let mut spans: Vec<(usize, usize, Face)> = pattern
+                    .as_ref()
+                    .map(|p| match p {
+                        Pattern::Plain(query) => {
+                            let margin = query.chars().count();
+                            let shown =
+                                display::idx(&line, left, self.tab_width);
+                            let lo = shown.saturating_sub(margin);
+                            let hi = line.chars().count() + margin;
+                            let part = buf.line_slice(y, lo, hi);
+                            let lo = min(lo, buf.line_len(y));
+                            p.matches(&part)
+                                .into_iter()
+                                .map(|(start, len)| (lo + start, len))
+                                .collect()
+                        }
+                        Pattern::Regex(_) => p.matches(&buf.line(y)),
+                    })
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(start, len)| {
+                        let current = y == win.cy && start == win.cx;
+                        let face = if current {
+                            Face::CurrentMatch
+                        } else {
+                            Face::Match
+                        };
+                        (start, start + len, face)
+                    })
+                    .collect();
+                if let Some(((sx, sy), (ex, ey))) = selection {
+                    if sy <= y && y <= ey {
+                        let start = if y == sy { sx } else { 0 };
+                        let end = if y == ey { ex } else { buf.line_len(y) };
+                        spans.push((start, end, Face::Selection));
+                    }
+                }
+                // Highlighting hides the glyph's own face
+                let show_whitespace = self.show_whitespace;
+                let face_of = |g: &display::Glyph| {
+                    let span = spans
+                        .iter()
+                        .rev()
+                        .find(|&&(a, b, _)| a <= g.idx && g.idx < b);
+                    match (span, g.face) {
+                        (Some(&(_, _, face)), _) => face,
+                        (None, Face::Whitespace) if !show_whitespace => {
+                            Face::Normal
+                        }
+                        (None, face) => face,
+                    }
+                };
+                let mut col = left;
+                let trailing = buf.trailing_whitespace(y);
+                for g in display::glyphs(&line, self.tab_width, trailing) {
+                    if g.col + g.width <= left {
+                        continue;
+                    }
+                    if g.col >= right {
+                        break;
+                    }
+                    let face = face_of(&g);
+                    let x = text.x + col - left;
+                    let end = min(g.col + g.width, right);
+                    if g.col < left || end < g.col + g.width {
+                        // Wide glyph cut off by the window edge
+                        screen.fill(x, sy, end - col, face);
+                    } else if show_whitespace {
+                        screen.put(x, sy, &g.marker(), g.width, face);
+                    } else {
+                        screen.put(x, sy, &g.text, g.width, face);
+                    }
+                    col = end;
+                }
+                screen.fill(
+                    text.x + col - left,
+                    sy,
+                    right - col,
+                    Face::Normal,
+                );
+            } else {
+                screen.put(text.x, sy, "~", 1, Face::Decoration);
+                screen.fill(text.x + 1, sy, text.w - 1, Face::Normal);
+            }
+        }
 
-        let status = format!(
-            "? {fname} {line}:{col} -- {message}",
-            fname = self.fname,
-            col = self.cx,
-            line = self.cy + 1,
-            message = self.message,
+        let mut status = format!(
+            "{mode}{flag} {fname} {line}:{col}",
+            mode = match &self.vi {
+                Some(vi) => format!("{} ", vi.mode),
+                None => String::new(),
+            },
+            flag = if buf.modified { "*" } else { "-" },
+            fname = buf.name,
+            col = win.cx,
+            line = win.cy + 1,
         );
-        self.term
-            .stdout
-            .queue(crossterm::cursor::Hide)?
-            .queue(crossterm::cursor::MoveTo(0, 0))?;
-        for y in self.oy..min(self.oy + self.term.wy, self.lines.len()) {
-            let line = &self.lines[y];
-            let line = line.uslice(self.ox, self.ox + self.term.wx + 1);
-            self.term
-                .stdout
-                .queue(Clear(ClearType::CurrentLine))?
-                .queue(style::Print(line))?
-                .queue(style::Print("\r\n"))?
-                .queue(style::ResetColor)?;
-        }
-        self.term
-            .stdout
-            .queue(style::SetForegroundColor(style::Color::Blue))?;
-        for _y in min(self.oy + self.term.wy, self.lines.len())
-            ..(self.oy + self.term.wy)
-        {
-            self.term
-                .stdout
-                .queue(Clear(ClearType::CurrentLine))?
-                .queue(style::Print("~\r\n"))?;
-        }
-        self.term
-            .stdout
-            .queue(style::SetBackgroundColor(style::Color::Blue))?
-            .queue(style::SetForegroundColor(style::Color::Black))?
-            .queue(style::Print(status.uslice(0, self.term.wx)))?;
-        for _i in status.ulen()..(self.term.wx + 1) {
-            self.term.stdout.queue(style::Print(" "))?;
-        }
-        self.term
-            .stdout
-            .queue(style::ResetColor)?
-            .queue(crossterm::cursor::MoveTo(
-                (self.cx - self.ox) as u16,
-                (self.cy - self.oy) as u16,
-            ))?
-            .queue(crossterm::cursor::Show)?
-            .flush()?;
-        Ok(())
+        let color = if w == self.focus {
+            status.push_str(" -- ");
+            let typed = self.vi.as_ref().map_or("", |vi| vi.typed.as_str());
+            if !self.pending.is_empty() {
+                status.push_str(&keys::display_seq(&self.pending));
+                status.push('-');
+            } else if !typed.is_empty() {
+                status.push_str(typed);
+            } else {
+                status.push_str(&self.message);
+            }
+            Face::Status
+        } else {
+            Face::StatusInactive
+        };
+        let status = prompt.unwrap_or(status);
+        screen.print(rect.x, rect.y + height, &status, rect.w, color);
     }
 }
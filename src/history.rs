@@ -0,0 +1,124 @@
+/// A single reversible change to a buffer. Positions are char indices into
+/// the whole buffer, as they were at the time the change was made.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Insert { at: usize, text: String },
+    Remove { at: usize, text: String },
+}
+
+impl Edit {
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { at, text } => Edit::Remove {
+                at: *at,
+                text: text.clone(),
+            },
+            Edit::Remove { at, text } => Edit::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+/// One undo step: everything a single command (or a group of consecutive
+/// commands) did, and where the cursor was before and after.
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub edits: Vec<Edit>,
+    pub before: (usize, usize),
+    pub after: (usize, usize),
+}
+
+/// Undo / redo stacks for a buffer.
+///
+/// Edits are recorded into the most recent step for as long as it is open.
+/// `seal` closes the step, so that the next edit starts a new one; callers
+/// that want to group commands simply refrain from sealing in between.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    open: bool,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Start a new step (unless one is still open), remembering the cursor
+    /// position to restore on undo.
+    pub fn begin(&mut self, cursor: (usize, usize)) {
+        if !self.open {
+            self.undo.push(Step {
+                edits: Vec::new(),
+                before: cursor,
+                after: cursor,
+            });
+            self.open = true;
+        }
+    }
+
+    /// Record the cursor position to restore on redo. Drops the current
+    /// step if nothing was recorded into it.
+    pub fn end(&mut self, cursor: (usize, usize)) {
+        if !self.open {
+            return;
+        }
+        match self.undo.last_mut() {
+            Some(step) if step.edits.is_empty() => {
+                self.undo.pop();
+                self.open = false;
+            }
+            Some(step) => step.after = cursor,
+            None => (),
+        }
+    }
+
+    /// Close the current step.
+    pub fn seal(&mut self) {
+        if self.open && self.undo.last().is_some_and(|s| s.edits.is_empty()) {
+            self.undo.pop();
+        }
+        self.open = false;
+    }
+
+    pub fn record(&mut self, edit: Edit, cursor: (usize, usize)) {
+        self.redo.clear();
+        self.begin(cursor);
+        let step = self.undo.last_mut().expect("begin");
+        // Typing a word should not cost one step per character
+        if let (
+            Some(Edit::Insert {
+                at: at0,
+                text: text0,
+            }),
+            Edit::Insert { at, text },
+        ) = (step.edits.last_mut(), &edit)
+        {
+            if *at == *at0 + text0.chars().count() {
+                text0.push_str(text);
+                return;
+            }
+        }
+        step.edits.push(edit);
+    }
+
+    /// Pop the most recent step for undoing. The caller applies the inverse
+    /// of its edits, in reverse order.
+    pub fn undo(&mut self) -> Option<Step> {
+        self.seal();
+        let step = self.undo.pop()?;
+        self.redo.push(step.clone());
+        Some(step)
+    }
+
+    /// Pop the most recently undone step for redoing.
+    pub fn redo(&mut self) -> Option<Step> {
+        self.seal();
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        Some(step)
+    }
+}
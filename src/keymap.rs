@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyEvent;
+
+use crate::editor::Command;
+
+/// What a key is bound to: a command, or more keys to read.
+#[derive(Debug, Clone)]
+enum Binding {
+    Command(Command),
+    Prefix(Keymap),
+}
+
+/// Result of looking up a key sequence.
+#[derive(Debug, PartialEq)]
+pub enum Lookup<'a> {
+    Command(&'a Command),
+    // The sequence is the start of longer ones, wait for the next key
+    Prefix,
+    Unbound,
+}
+
+/// Key bindings, shaped as a trie so that sequences of keys (chords like
+/// "c-x c-s") can be bound as well as single keys.
+#[derive(Debug, Clone, Default)]
+pub struct Keymap {
+    map: HashMap<KeyEvent, Binding>,
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        Keymap::default()
+    }
+
+    /// Bind seq to cmd. Binding a sequence replaces any binding of its
+    /// prefixes, and any longer sequences starting with it.
+    pub fn bind(&mut self, seq: Vec<KeyEvent>, cmd: Command) {
+        let (last, init) = match seq.split_last() {
+            Some(split) => split,
+            None => return,
+        };
+        let mut map = self;
+        for k in init {
+            let binding = map
+                .map
+                .entry(*k)
+                .or_insert_with(|| Binding::Prefix(Keymap::new()));
+            if let Binding::Command(_) = binding {
+                *binding = Binding::Prefix(Keymap::new());
+            }
+            map = match binding {
+                Binding::Prefix(map) => map,
+                Binding::Command(_) => unreachable!(),
+            };
+        }
+        map.map.insert(*last, Binding::Command(cmd));
+    }
+
+    pub fn lookup(&self, seq: &[KeyEvent]) -> Lookup<'_> {
+        let mut map = self;
+        for (i, k) in seq.iter().enumerate() {
+            match map.map.get(k) {
+                None => return Lookup::Unbound,
+                Some(Binding::Command(cmd)) if i + 1 == seq.len() => {
+                    return Lookup::Command(cmd)
+                }
+                Some(Binding::Command(_)) => return Lookup::Unbound,
+                Some(Binding::Prefix(next)) => map = next,
+            }
+        }
+        if seq.is_empty() {
+            Lookup::Unbound
+        } else {
+            Lookup::Prefix
+        }
+    }
+}
@@ -20,6 +20,8 @@ fn _parse_code(s: &str) -> Option<KeyCode> {
             "tab" => Some(KeyCode::Tab),
             "backtab" | "back-tab" => Some(KeyCode::BackTab),
             "esc" | "escape" => Some(KeyCode::Esc),
+            "space" => Some(KeyCode::Char(' ')),
+            "null" => Some(KeyCode::Null),
             _ => {
                 // f1-f12 (f0-f255)
                 if s.chars().next().expect("s.len") == 'f' {
@@ -51,23 +53,18 @@ fn _parse_mod(s: &str) -> Option<KeyModifiers> {
 pub fn parse(s: &str) -> Option<KeyEvent> {
     let ss: Vec<&str> = s.split("-").collect();
     let mut mods = KeyModifiers::empty();
-    if ss.len() == 0 {
+    if ss.is_empty() {
         return None;
     }
     if ss.len() >= 2 {
         for x in &ss[..ss.len() - 1] {
-            if let Some(m) = _parse_mod(x) {
-                mods.insert(m);
-            }
+            mods.insert(_parse_mod(x)?);
         }
     }
-    match _parse_code(ss.last().expect("ss.len")) {
-        Some(c) => Some(KeyEvent {
-            code: c,
-            modifiers: mods,
-        }),
-        None => None,
-    }
+    Some(KeyEvent::new(
+        _parse_code(ss.last().expect("ss.len"))?,
+        mods,
+    ))
 }
 
 pub fn must_parse(s: &str) -> KeyEvent {
@@ -77,6 +74,24 @@ pub fn must_parse(s: &str) -> KeyEvent {
     }
 }
 
+/// Parse a sequence of keys separated by whitespace, e.g. "c-x c-s".
+pub fn parse_seq(s: &str) -> Option<Vec<KeyEvent>> {
+    let seq: Option<Vec<KeyEvent>> = s.split_whitespace().map(parse).collect();
+    seq.filter(|seq| !seq.is_empty())
+}
+
+pub fn must_parse_seq(s: &str) -> Vec<KeyEvent> {
+    match parse_seq(s) {
+        Some(seq) => seq,
+        None => panic!("Cannot parse: {:?}", s),
+    }
+}
+
+pub fn display_seq(seq: &[KeyEvent]) -> String {
+    let keys: Vec<String> = seq.iter().map(|k| display(*k)).collect();
+    keys.join(" ")
+}
+
 pub fn display(ke: KeyEvent) -> String {
     format!(
         "{mods}{key}",
@@ -110,8 +125,10 @@ pub fn display(ke: KeyEvent) -> String {
             KeyCode::BackTab => "backtab".into(),
             KeyCode::Esc => "esc".into(),
             KeyCode::Null => "null".into(),
+            KeyCode::Char(' ') => "space".into(),
             KeyCode::Char(c) => c.to_string(),
             KeyCode::F(i) => format!("f{}", i),
+            c => format!("{:?}", c).to_ascii_lowercase(),
         },
     )
 }
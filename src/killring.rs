@@ -0,0 +1,61 @@
+/// Text removed by kill commands, most recent last, for yanking back. Only
+/// the most recent `cap` kills are kept.
+#[derive(Debug, Clone)]
+pub struct KillRing {
+    entries: Vec<String>,
+    cap: usize,
+    // The entry last yanked, counting back from the most recent one
+    yanked: usize,
+}
+
+impl KillRing {
+    pub fn new(cap: usize) -> KillRing {
+        KillRing {
+            entries: Vec::new(),
+            cap: cap.max(1),
+            yanked: 0,
+        }
+    }
+
+    /// Add a new entry, dropping the oldest one if the ring is full.
+    pub fn push(&mut self, text: String) {
+        if self.entries.len() == self.cap {
+            self.entries.remove(0);
+        }
+        self.entries.push(text);
+        self.yanked = 0;
+    }
+
+    /// Add text to the most recent entry, at its start when it was killed
+    /// backwards. Starts a new entry if there is none.
+    pub fn append(&mut self, text: &str, before: bool) {
+        match self.entries.last_mut() {
+            Some(last) if before => last.insert_str(0, text),
+            Some(last) => last.push_str(text),
+            None => self.push(text.into()),
+        }
+        self.yanked = 0;
+    }
+
+    /// The most recent entry.
+    pub fn latest(&self) -> Option<&str> {
+        self.entries.last().map(|s| s.as_str())
+    }
+
+    /// The most recent entry, to be followed by `yank_pop`.
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yanked = 0;
+        self.latest()
+    }
+
+    /// The entry before the one last yanked, going round to the most recent
+    /// one after the oldest.
+    pub fn yank_pop(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.yanked = (self.yanked + 1) % self.entries.len();
+        let idx = self.entries.len() - 1 - self.yanked;
+        Some(&self.entries[idx])
+    }
+}
@@ -7,14 +7,41 @@ use std::io;
 use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::path::Path;
 
+mod buffer;
+mod clipboard;
+mod commands;
+mod config;
+mod display;
 mod editor;
+mod history;
+mod keymap;
 mod keys;
+mod killring;
+mod prompt;
+mod screen;
+mod search;
 mod tests;
 mod tty;
 mod utils;
+mod vi;
+mod window;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    // --keymap NAME picks the keymap; everything else is a file to open
+    let mut keymap = None;
+    let mut files = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--keymap" {
+            keymap = Some(args.next().ok_or_else(|| {
+                anyhow!("--keymap needs the name of a keymap")
+            })?);
+        } else if let Some(name) = arg.strip_prefix("--keymap=") {
+            keymap = Some(name.to_string());
+        } else {
+            files.push(arg);
+        }
+    }
 
     if !tty::is_tty(&io::stdin()) {
         return Err(anyhow!("Standard input is not a TTY."));
@@ -25,11 +52,11 @@ fn main() -> Result<()> {
 
     let t = tty::Terminal::new(io::stdout())?;
     let mut e = editor::Editor::new(t);
+    e.load_config(keymap.as_deref())?;
 
     let r = catch_unwind(AssertUnwindSafe(|| {
-        if args.len() == 2 {
-            let path = Path::new(&args[1]);
-            e.open(path).expect("open");
+        for file in &files {
+            e.open(Path::new(file)).expect("open");
         }
         e.init().unwrap();
         loop {
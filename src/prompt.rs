@@ -0,0 +1,243 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::display;
+use crate::utils::*;
+
+/// What a prompt asks for. Each kind keeps its own input history, and
+/// decides what Tab completes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    File,
+    Line,
+    Buffer,
+    Search,
+    Replace,
+    ReplaceWith,
+    Command,
+}
+
+/// Result of feeding a key to a prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pending,
+    Done(String),
+    Cancelled,
+    // The key has no meaning in a prompt
+    Ignored,
+}
+
+/// A line of text input, read in the status line.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub label: String,
+    pub text: String,
+    // Cursor position in text, in chars
+    pub pos: usize,
+    // Shown after the text, e.g. completion candidates
+    pub hint: String,
+    // Position in the history while browsing it, and the text typed
+    // before browsing started
+    history_pos: Option<usize>,
+    typed: String,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, label: &str) -> Prompt {
+        Prompt {
+            kind,
+            label: label.into(),
+            text: String::new(),
+            pos: 0,
+            hint: String::new(),
+            history_pos: None,
+            typed: String::new(),
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.into();
+        self.pos = text.ulen();
+    }
+
+    /// Handle a key. History is the list of earlier inputs for this kind
+    /// of prompt, oldest first; complete returns the candidates for Tab.
+    pub fn key(
+        &mut self,
+        k: KeyEvent,
+        history: &[String],
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> Outcome {
+        let len = self.text.ulen();
+        let ctrl = k.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = k.modifiers.contains(KeyModifiers::ALT);
+        self.hint.clear();
+        match k.code {
+            KeyCode::Enter => return Outcome::Done(self.text.clone()),
+            KeyCode::Esc => return Outcome::Cancelled,
+            KeyCode::Char('g') if ctrl => return Outcome::Cancelled,
+            KeyCode::Char('a') if ctrl => self.pos = 0,
+            KeyCode::Char('e') if ctrl => self.pos = len,
+            KeyCode::Char('k') if ctrl => {
+                self.text = self.text.uslice(0, self.pos);
+            }
+            KeyCode::Char('u') if ctrl => {
+                self.text = self.text.uslice(self.pos, len);
+                self.pos = 0;
+            }
+            KeyCode::Char(c) if !ctrl && !alt => {
+                let mut text = self.text.uslice(0, self.pos);
+                text.push(c);
+                text.push_str(&self.text.uslice(self.pos, len));
+                self.text = text;
+                self.pos += 1;
+            }
+            KeyCode::Home => self.pos = 0,
+            KeyCode::End => self.pos = len,
+            KeyCode::Left => self.pos = self.text.prev_grapheme(self.pos),
+            KeyCode::Right => self.pos = self.text.next_grapheme(self.pos),
+            KeyCode::Backspace if self.pos == 0 => (),
+            KeyCode::Backspace => {
+                let start = self.text.prev_grapheme(self.pos);
+                let mut text = self.text.uslice(0, start);
+                text.push_str(&self.text.uslice(self.pos, len));
+                self.text = text;
+                self.pos = start;
+            }
+            KeyCode::Delete if self.pos == len => (),
+            KeyCode::Delete => {
+                let end = self.text.next_grapheme(self.pos);
+                let mut text = self.text.uslice(0, self.pos);
+                text.push_str(&self.text.uslice(end, len));
+                self.text = text;
+            }
+            KeyCode::Up => {
+                let pos = match self.history_pos {
+                    None if history.is_empty() => return Outcome::Pending,
+                    None => {
+                        self.typed = self.text.clone();
+                        history.len() - 1
+                    }
+                    Some(pos) => pos.saturating_sub(1),
+                };
+                self.history_pos = Some(pos);
+                self.set_text(&history[pos]);
+            }
+            KeyCode::Down => match self.history_pos {
+                Some(pos) if pos + 1 < history.len() => {
+                    self.history_pos = Some(pos + 1);
+                    self.set_text(&history[pos + 1]);
+                }
+                Some(_) => {
+                    self.history_pos = None;
+                    let typed = self.typed.clone();
+                    self.set_text(&typed);
+                }
+                None => (),
+            },
+            KeyCode::Tab => {
+                let candidates = complete(&self.text);
+                match candidates.len() {
+                    0 => self.hint = " [no match]".into(),
+                    1 => self.set_text(&candidates[0]),
+                    _ => {
+                        let prefix = common_prefix(&candidates);
+                        if prefix.ulen() > self.text.ulen() {
+                            self.set_text(&prefix);
+                        } else {
+                            self.hint =
+                                format!(" {{{}}}", candidates.join(" | "));
+                        }
+                    }
+                }
+            }
+            _ => return Outcome::Ignored,
+        }
+        Outcome::Pending
+    }
+
+    /// The part of the prompt that fits in width cells, scrolled so that the
+    /// cursor stays visible, and the column of the cursor within it. A
+    /// glyph cut by either edge is left out.
+    pub fn render(&self, width: usize) -> (String, usize) {
+        let line = format!("{}{}{}", self.label, self.text, self.hint);
+        let glyphs = || display::glyphs(&line, 1, usize::MAX);
+        let cursor = display::col(&line, self.label.ulen() + self.pos, 1);
+        let start = (cursor + 1).saturating_sub(width);
+        let (from, left) = glyphs()
+            .find(|g| g.col >= start)
+            .map_or((line.ulen(), start), |g| (g.idx, g.col));
+        let to = glyphs()
+            .find(|g| g.col + g.width > start + width)
+            .map_or(line.ulen(), |g| g.idx);
+        (line.uslice(from, to), cursor - left)
+    }
+}
+
+/// Longest common prefix of all items.
+pub fn common_prefix(items: &[String]) -> String {
+    let mut prefix: Vec<char> = match items.first() {
+        Some(first) => first.chars().collect(),
+        None => return String::new(),
+    };
+    for item in &items[1..] {
+        let n = prefix
+            .iter()
+            .zip(item.chars())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(n);
+    }
+    prefix.into_iter().collect()
+}
+
+/// Add an entry to a prompt history, skipping empty input and repeats.
+pub fn remember(history: &mut Vec<String>, text: &str) {
+    if !text.is_empty() && history.last().map(|s| s.as_str()) != Some(text) {
+        history.push(text.into());
+    }
+}
+
+/// Turn text typed into a prompt into a path, expanding a leading "~/" to
+/// the home directory.
+pub fn expand_path(text: &str) -> PathBuf {
+    match (text.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(text),
+    }
+}
+
+/// Complete a path: entries of the directory named by everything up to the
+/// last slash, starting with the rest. Directories get a trailing slash.
+pub fn complete_path(text: &str) -> Vec<String> {
+    let (dir, base) = match text.rfind('/') {
+        Some(i) => (&text[..i + 1], &text[i + 1..]),
+        None => ("", text),
+    };
+    let read = if dir.is_empty() { "." } else { dir };
+    let mut out: Vec<String> = match fs::read_dir(expand_path(read)) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                if !name.starts_with(base)
+                    || (base.is_empty() && name.starts_with('.'))
+                {
+                    return None;
+                }
+                let slash = match e.file_type() {
+                    Ok(t) if t.is_dir() => "/",
+                    _ => "",
+                };
+                Some(format!("{}{}{}", dir, name, slash))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    out.sort();
+    out
+}
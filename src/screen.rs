@@ -0,0 +1,173 @@
+use anyhow::Result;
+use std::io::Write;
+
+use crossterm::{
+    cursor,
+    style::Print,
+    terminal::{Clear, ClearType},
+    QueueableCommand,
+};
+
+use crate::display::{self, Face};
+
+/// One character cell of the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    // What's printed in the cell; empty for the cells taken up by the rest
+    // of a double-width glyph
+    pub text: String,
+    pub face: Face,
+}
+
+impl Cell {
+    fn blank() -> Cell {
+        Cell {
+            text: String::from(" "),
+            face: Face::Normal,
+        }
+    }
+}
+
+/// The contents of the terminal. Frames are drawn into the back buffer, and
+/// `flush` sends only the cells that differ from the front buffer, which
+/// holds what the terminal shows.
+#[derive(Debug)]
+pub struct Screen {
+    pub w: usize,
+    pub h: usize,
+    front: Vec<Cell>,
+    back: Vec<Cell>,
+    // The terminal's contents are unknown, e.g. after a resize, so the next
+    // flush has to send everything
+    stale: bool,
+    // Where the cursor was left by the last flush
+    cursor: Option<(usize, usize)>,
+}
+
+impl Screen {
+    pub fn new(w: usize, h: usize) -> Screen {
+        Screen {
+            w,
+            h,
+            front: vec![Cell::blank(); w * h],
+            back: vec![Cell::blank(); w * h],
+            stale: true,
+            cursor: None,
+        }
+    }
+
+    /// Change the size of the screen, forgetting its contents.
+    pub fn resize(&mut self, w: usize, h: usize) {
+        if (w, h) != (self.w, self.h) {
+            *self = Screen::new(w, h);
+        }
+    }
+
+    /// Put a glyph width cells wide at (x, y), cut off at the edge of the
+    /// screen.
+    pub fn put(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: &str,
+        width: usize,
+        face: Face,
+    ) {
+        if y >= self.h {
+            return;
+        }
+        for i in 0..width.max(1) {
+            if x + i >= self.w {
+                break;
+            }
+            self.back[y * self.w + x + i] = Cell {
+                text: if i == 0 { text.into() } else { String::new() },
+                face,
+            };
+        }
+    }
+
+    /// Fill n cells from (x, y) with blanks.
+    pub fn fill(&mut self, x: usize, y: usize, n: usize, face: Face) {
+        for i in 0..n {
+            self.put(x + i, y, " ", 1, face);
+        }
+    }
+
+    /// Print text from (x, y), in at most w cells, and fill the rest of
+    /// them with blanks.
+    pub fn print(
+        &mut self,
+        x: usize,
+        y: usize,
+        text: &str,
+        w: usize,
+        face: Face,
+    ) {
+        let mut col = 0;
+        for g in display::glyphs(text, 1, usize::MAX) {
+            if g.col + g.width > w {
+                break;
+            }
+            self.put(x + g.col, y, &g.text, g.width, face);
+            col = g.col + g.width;
+        }
+        self.fill(x + col, y, w - col, face);
+    }
+
+    /// Send the cells changed since the last flush to out, and put the
+    /// cursor at (x, y).
+    pub fn flush<W: Write>(
+        &mut self,
+        out: &mut W,
+        (x, y): (usize, usize),
+    ) -> Result<()> {
+        let mut changed = false;
+        // Where the terminal's cursor is, and the face it prints in
+        let mut at = None;
+        let mut face = None;
+        if self.stale {
+            out.queue(cursor::Hide)?.queue(Clear(ClearType::All))?;
+            changed = true;
+        }
+        for (i, cell) in self.back.iter().enumerate() {
+            if !self.stale && self.front[i] == *cell {
+                continue;
+            }
+            if cell.text.is_empty() {
+                // Drawn along with the glyph it's part of
+                continue;
+            }
+            if !changed {
+                out.queue(cursor::Hide)?;
+                changed = true;
+            }
+            let (cx, cy) = (i % self.w, i / self.w);
+            if at != Some((cx, cy)) {
+                out.queue(cursor::MoveTo(cx as u16, cy as u16))?;
+            }
+            if face != Some(cell.face) {
+                cell.face.queue(out)?;
+                face = Some(cell.face);
+            }
+            out.queue(Print(&cell.text))?;
+            let width = 1 + self.back[i + 1..]
+                .iter()
+                .take_while(|c| c.text.is_empty())
+                .count();
+            at = Some((cx + width, cy));
+        }
+        if face.is_some() {
+            Face::Normal.queue(out)?;
+        }
+        if changed || self.cursor != Some((x, y)) {
+            out.queue(cursor::MoveTo(x as u16, y as u16))?
+                .queue(cursor::Show)?;
+        }
+        out.flush()?;
+        self.front.clone_from(&self.back);
+        self.stale = false;
+        self.cursor = Some((x, y));
+        Ok(())
+    }
+}
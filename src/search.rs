@@ -0,0 +1,368 @@
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::buffer::Buffer;
+use crate::prompt::{Prompt, PromptKind};
+use crate::window::Window;
+
+/// State of an incremental search in progress. The query is read through
+/// a prompt, whose label tells how the search is going.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub prompt: Prompt,
+    pub forward: bool,
+    pub regex: bool,
+    // Window state to restore when the search is cancelled
+    pub origin: Window,
+    pub failed: bool,
+    pub wrapped: bool,
+    // The query as last compiled, and what it compiled to
+    compiled: String,
+    pattern: Result<Pattern, regex::Error>,
+    pub haystack: Haystack,
+}
+
+impl Search {
+    pub fn new(forward: bool, regex: bool, origin: Window) -> Search {
+        let mut search = Search {
+            prompt: Prompt::new(PromptKind::Search, ""),
+            forward,
+            regex,
+            origin,
+            failed: false,
+            wrapped: false,
+            compiled: String::new(),
+            pattern: Pattern::new("", regex),
+            haystack: Haystack::default(),
+        };
+        search.update_prompt();
+        search
+    }
+
+    pub fn query(&self) -> &str {
+        &self.prompt.text
+    }
+
+    /// The query compiled, as of the last call to `compile`.
+    pub fn pattern(&self) -> Result<&Pattern, &regex::Error> {
+        self.pattern.as_ref()
+    }
+
+    /// Compile the query, unless it hasn't changed since the last time.
+    pub fn compile(&mut self) {
+        if self.compiled != self.prompt.text {
+            self.compiled = self.prompt.text.clone();
+            self.pattern = Pattern::new(&self.compiled, self.regex);
+        }
+    }
+
+    /// Bring the prompt label and the compiled query up to date with the
+    /// state of the search.
+    pub fn update_prompt(&mut self) {
+        self.compile();
+        self.prompt.label = format!(
+            "{failing}{wrapped}{regex}I-search{backward}: ",
+            failing = if self.failed { "Failing " } else { "" },
+            wrapped = if self.wrapped { "Wrapped " } else { "" },
+            regex = if self.regex { "Regexp " } else { "" },
+            backward = if self.forward { "" } else { " backward" },
+        );
+        if self.pattern().is_err() {
+            self.prompt.hint = " [incomplete regexp]".into();
+        }
+    }
+}
+
+/// A compiled search query.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Plain(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn new(query: &str, regex: bool) -> Result<Pattern, regex::Error> {
+        if regex {
+            Ok(Pattern::Regex(Regex::new(query)?))
+        } else {
+            Ok(Pattern::Plain(query.into()))
+        }
+    }
+
+    /// Whether the pattern can match a line ending, and so across line
+    /// boundaries, which means it has to be run on the whole buffer
+    /// instead of line by line.
+    fn multiline(&self) -> bool {
+        match self {
+            Pattern::Plain(_) => false,
+            Pattern::Regex(re) => regex_syntax::parse(re.as_str())
+                .map_or(true, |hir| matches_newline(&hir)),
+        }
+    }
+
+    /// All non-overlapping matches in line, as (start, length) pairs in
+    /// chars.
+    pub fn matches(&self, line: &str) -> Vec<(usize, usize)> {
+        match self {
+            Pattern::Plain(query) => matches(line, query),
+            Pattern::Regex(re) => re
+                .find_iter(line)
+                .filter(|m| !m.as_str().is_empty())
+                .map(|m| {
+                    let start = line[..m.start()].chars().count();
+                    (start, m.as_str().chars().count())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether any part of a parsed regex can match a newline: a literal, or
+/// a class such as \s or [^a] that includes it.
+fn matches_newline(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(lit) => lit.0.contains(&b'\n'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .any(|r| r.start() <= '\n' && '\n' <= r.end()),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .any(|r| r.start() <= b'\n' && b'\n' <= r.end()),
+        HirKind::Repetition(rep) => matches_newline(&rep.sub),
+        HirKind::Capture(cap) => matches_newline(&cap.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+            hirs.iter().any(matches_newline)
+        }
+    }
+}
+
+/// The whole text of a buffer, for running patterns that span lines. It's
+/// copied on first use and kept for the rest of a search or query-replace,
+/// so the buffer mustn't change meanwhile other than through `replace`.
+#[derive(Debug, Clone, Default)]
+pub struct Haystack {
+    text: Option<String>,
+}
+
+impl Haystack {
+    fn text(&mut self, buf: &Buffer) -> &str {
+        self.text.get_or_insert_with(|| buf.text())
+    }
+
+    /// Make the same change to the copy as was made to the buffer: replace
+    /// bytes start..end with text.
+    pub fn replace(&mut self, start: usize, end: usize, text: &str) {
+        if let Some(copy) = &mut self.text {
+            copy.replace_range(start..end, text);
+        }
+    }
+}
+
+fn fold(ch: char) -> char {
+    ch.to_lowercase().next().unwrap_or(ch)
+}
+
+/// All non-overlapping matches of query in line, as (start, length) pairs
+/// in chars. The match is case-insensitive unless the query contains an
+/// upper case letter.
+pub fn matches(line: &str, query: &str) -> Vec<(usize, usize)> {
+    let q: Vec<char> = query.chars().collect();
+    if q.is_empty() {
+        return Vec::new();
+    }
+    let ignore_case = !q.iter().any(|c| c.is_uppercase());
+    let eq = |a: char, b: char| a == b || (ignore_case && fold(a) == fold(b));
+    let l: Vec<char> = line.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i + q.len() <= l.len() {
+        if l[i..i + q.len()]
+            .iter()
+            .zip(q.iter())
+            .all(|(a, b)| eq(*a, *b))
+        {
+            out.push((i, q.len()));
+            i += q.len();
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+fn find_from(
+    buf: &Buffer,
+    pattern: &Pattern,
+    from: usize,
+    forward: bool,
+    haystack: &mut Haystack,
+) -> Option<usize> {
+    if let Pattern::Regex(re) = pattern {
+        if pattern.multiline() {
+            // Run on the whole buffer, for patterns that span lines
+            let byte = buf.char_to_byte(from.min(buf.len_chars()));
+            let mut starts = re
+                .find_iter(haystack.text(buf))
+                .filter(|m| !m.as_str().is_empty())
+                .map(|m| m.start());
+            let found = if forward {
+                starts.find(|&b| b >= byte)
+            } else {
+                starts.take_while(|&b| b <= byte).last()
+            };
+            return found.map(|b| buf.byte_to_char(b));
+        }
+    }
+    let (fx, fy) = buf.char_to_pos(from);
+    if forward {
+        for y in fy..buf.len_lines() {
+            for (start, _) in pattern.matches(&buf.line(y)) {
+                if y > fy || start >= fx {
+                    return Some(buf.pos_to_char(start, y));
+                }
+            }
+        }
+    } else {
+        for y in (0..=fy).rev() {
+            for (start, _) in pattern.matches(&buf.line(y)).into_iter().rev() {
+                if y < fy || start <= fx {
+                    return Some(buf.pos_to_char(start, y));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Find the first match of pattern starting at or after char index from
+/// (or at or before, when searching backward), wrapping around the end of
+/// the buffer. Returns the char index of the match, and whether the search
+/// had to wrap around.
+pub fn find(
+    buf: &Buffer,
+    pattern: &Pattern,
+    from: usize,
+    forward: bool,
+    haystack: &mut Haystack,
+) -> Option<(usize, bool)> {
+    if let Some(idx) = find_from(buf, pattern, from, forward, haystack) {
+        return Some((idx, false));
+    }
+    let restart = if forward { 0 } else { buf.len_chars() };
+    find_from(buf, pattern, restart, forward, haystack).map(|idx| (idx, true))
+}
+
+/// A match found for replacing: its char range in the buffer, and the
+/// replacement text with capture group references ($1, ${name}) expanded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// Find the first match of re starting at or after char index from, without
+/// wrapping around. An empty match at prev_end, the end of the previous
+/// match, is skipped, as it would be the same place again.
+pub fn find_replace(
+    buf: &Buffer,
+    re: &Regex,
+    replacement: &str,
+    from: usize,
+    prev_end: Option<usize>,
+    haystack: &mut Haystack,
+) -> Option<Found> {
+    let mut from = from;
+    loop {
+        let found = find_replace_from(buf, re, replacement, from, haystack)?;
+        if found.start == found.end && Some(found.start) == prev_end {
+            from = step(buf, found.start);
+            continue;
+        }
+        return Some(found);
+    }
+}
+
+fn find_replace_from(
+    buf: &Buffer,
+    re: &Regex,
+    replacement: &str,
+    from: usize,
+    haystack: &mut Haystack,
+) -> Option<Found> {
+    if from > buf.len_chars() {
+        return None;
+    }
+    let pattern = Pattern::Regex(re.clone());
+    if pattern.multiline() {
+        let text = haystack.text(buf);
+        let caps = re.captures_at(text, buf.char_to_byte(from))?;
+        let m = caps.get(0).expect("group 0");
+        let mut expanded = String::new();
+        caps.expand(replacement, &mut expanded);
+        return Some(Found {
+            start: buf.byte_to_char(m.start()),
+            end: buf.byte_to_char(m.end()),
+            replacement: expanded,
+        });
+    }
+    let (fx, fy) = buf.char_to_pos(from);
+    for y in fy..buf.len_lines() {
+        let line = buf.line(y);
+        let offset = if y == fy {
+            line.char_indices().nth(fx).map_or(line.len(), |(i, _)| i)
+        } else {
+            0
+        };
+        if let Some(caps) = re.captures_at(&line, offset) {
+            let m = caps.get(0).expect("group 0");
+            let mut expanded = String::new();
+            caps.expand(replacement, &mut expanded);
+            let start = line[..m.start()].chars().count();
+            let len = m.as_str().chars().count();
+            return Some(Found {
+                start: buf.pos_to_char(start, y),
+                end: buf.pos_to_char(start + len, y),
+                replacement: expanded,
+            });
+        }
+    }
+    None
+}
+
+/// The char index just past idx, for stepping over an empty match. At the
+/// end of a line that's the start of the next one, since a line ending
+/// has no positions of its own.
+fn step(buf: &Buffer, idx: usize) -> usize {
+    let (x, y) = buf.char_to_pos(idx);
+    if x < buf.line_len(y) {
+        buf.pos_to_char(x + 1, y)
+    } else if y + 1 < buf.len_lines() {
+        buf.pos_to_char(0, y + 1)
+    } else {
+        buf.len_chars() + 1
+    }
+}
+
+/// State of an interactive query-replace, asking about each match in turn.
+#[derive(Debug, Clone)]
+pub struct Replace {
+    pub re: Regex,
+    pub replacement: String,
+    pub found: Found,
+    pub count: usize,
+    pub haystack: Haystack,
+}
+
+impl Replace {
+    /// Text for the status line.
+    pub fn prompt(&self) -> String {
+        format!(
+            "Replace with {}? (y/n/!/q) {} replaced",
+            self.found.replacement, self.count
+        )
+    }
+}
@@ -1,2 +1,15 @@
+mod test_buffer;
+mod test_clipboard;
+mod test_commands;
+mod test_config;
+mod test_display;
+mod test_history;
+mod test_keymap;
 mod test_keys;
+mod test_killring;
+mod test_prompt;
+mod test_screen;
+mod test_search;
 mod test_utils;
+mod test_vi;
+mod test_window;
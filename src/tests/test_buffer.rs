@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::*;
+
+    #[test]
+    fn test_lines() {
+        let b = Buffer::new();
+        assert_eq!(b.len_lines(), 1);
+        assert_eq!(b.line(0), "");
+
+        let b = Buffer::from_str("ab\ncd\n");
+        assert_eq!(b.len_lines(), 3);
+        assert_eq!(b.line(0), "ab");
+        assert_eq!(b.line(1), "cd");
+        assert_eq!(b.line(2), "");
+
+        let b = Buffer::from_str("ab\r\nκαλη");
+        assert_eq!(b.len_lines(), 2);
+        assert_eq!(b.line(0), "ab");
+        assert_eq!(b.line_len(0), 2);
+        assert_eq!(b.line_len(1), 4);
+    }
+
+    #[test]
+    fn test_line_slice() {
+        let b = Buffer::from_str("καλημέρα\n");
+        assert_eq!(b.line_slice(0, 0, 4), "καλη");
+        assert_eq!(b.line_slice(0, 4, 100), "μέρα");
+        assert_eq!(b.line_slice(0, 100, 200), "");
+        assert_eq!(b.line_slice(1, 0, 10), "");
+    }
+
+    #[test]
+    fn test_graphemes() {
+        // e + combining acute, then a flag made of two regional indicators
+        let b = Buffer::from_str("ae\u{301}\u{1f1ec}\u{1f1f7}b\r\n");
+        assert_eq!(b.next_grapheme(0, 0), 1);
+        assert_eq!(b.next_grapheme(1, 0), 3);
+        assert_eq!(b.next_grapheme(3, 0), 5);
+        assert_eq!(b.next_grapheme(6, 0), 6);
+        assert_eq!(b.prev_grapheme(5, 0), 3);
+        assert_eq!(b.prev_grapheme(3, 0), 1);
+        assert_eq!(b.prev_grapheme(0, 0), 0);
+        assert_eq!(b.next_grapheme(0, 1), 0);
+
+        // A family emoji (ZWJ sequence)
+        let b = Buffer::from_str("e\u{301}\u{1f468}\u{200d}\u{1f469}x");
+        assert_eq!(b.next_grapheme(2, 0), 5);
+        assert_eq!(b.prev_grapheme(6, 0), 5);
+        assert_eq!(b.prev_grapheme(5, 0), 2);
+        assert_eq!(b.prev_grapheme(1, 0), 0);
+        assert_eq!(b.grapheme_floor(1, 0), 0);
+        assert_eq!(b.grapheme_floor(3, 0), 2);
+        assert_eq!(b.grapheme_floor(5, 0), 5);
+        assert_eq!(b.grapheme_floor(9, 0), 6);
+
+        // Clusters across the rope's chunk boundaries
+        let line = "e\u{301}".repeat(5000);
+        let b = Buffer::from_str(&line);
+        let mut x = 0;
+        while x < 10000 {
+            let next = b.next_grapheme(x, 0);
+            assert_eq!(next, x + 2);
+            assert_eq!(b.prev_grapheme(next, 0), x);
+            x = next;
+        }
+    }
+
+    #[test]
+    fn test_pos_char() {
+        let b = Buffer::from_str("ab\r\ncd\n");
+        assert_eq!(b.pos_to_char(0, 0), 0);
+        assert_eq!(b.pos_to_char(2, 0), 2);
+        assert_eq!(b.pos_to_char(9, 0), 2);
+        assert_eq!(b.pos_to_char(0, 1), 4);
+        assert_eq!(b.pos_to_char(0, 9), 7);
+        assert_eq!(b.char_to_pos(0), (0, 0));
+        assert_eq!(b.char_to_pos(3), (2, 0));
+        assert_eq!(b.char_to_pos(4), (0, 1));
+        assert_eq!(b.char_to_pos(7), (0, 2));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut b = Buffer::new();
+        assert_eq!(b.insert(0, 0, "ac"), (2, 0));
+        assert_eq!(b.insert(1, 0, "b"), (2, 0));
+        assert_eq!(b.line(0), "abc");
+        assert_eq!(b.insert(1, 0, "\n"), (0, 1));
+        assert_eq!(b.line(0), "a");
+        assert_eq!(b.line(1), "bc");
+        assert_eq!(b.insert(2, 1, "d\ne"), (1, 2));
+        assert_eq!(b.line(2), "e");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut b = Buffer::from_str("ab\r\ncd\nef");
+        assert_eq!(b.remove((2, 0), (0, 1)), "\r\n");
+        assert_eq!(b.line(0), "abcd");
+        assert_eq!(b.remove((1, 1), (1, 0)), "bcd\ne");
+        assert_eq!(b.line(0), "af");
+        assert_eq!(b.len_lines(), 1);
+    }
+
+    #[test]
+    fn test_modified() {
+        // Edits that change nothing leave the buffer unmodified
+        let mut b = Buffer::from_str("ab");
+        assert_eq!(b.remove((0, 0), (0, 0)), "");
+        assert_eq!(b.remove((2, 0), (5, 0)), "");
+        assert_eq!(b.insert(1, 0, ""), (1, 0));
+        assert_eq!(b.undo(), None);
+        assert_eq!(b.redo(), None);
+        assert!(!b.modified);
+        b.insert(1, 0, "x");
+        assert!(b.modified);
+        b.modified = false;
+        b.undo();
+        assert!(b.modified);
+    }
+
+    #[test]
+    fn test_words() {
+        let b = Buffer::from_str("foo_1, bar\n  baz");
+        assert_eq!(b.word_end(0), 5);
+        assert_eq!(b.word_end(5), 10);
+        assert_eq!(b.word_end(10), 16);
+        assert_eq!(b.word_end(16), 16);
+        assert_eq!(b.word_start(16), 13);
+        assert_eq!(b.word_start(13), 7);
+        assert_eq!(b.word_start(7), 0);
+        assert_eq!(b.word_start(0), 0);
+
+        let b = Buffer::from_str("καλη-μέρα_σας");
+        assert_eq!(b.word_end(0), 4);
+        assert_eq!(b.word_end(4), 13);
+        assert_eq!(b.word_start(13), 5);
+
+        // Apostrophes and decimal points don't end a word, and each
+        // ideograph is one
+        let b = Buffer::from_str("can't 3.14 中文");
+        assert_eq!(b.word_end(0), 5);
+        assert_eq!(b.word_end(5), 10);
+        assert_eq!(b.word_end(10), 12);
+        assert_eq!(b.word_start(13), 12);
+        assert_eq!(b.word_start(11), 6);
+        assert_eq!(b.word_start(4), 0);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let b = Buffer::from_str("a\nb\n\n  \nc\nd\n\ne");
+        assert_eq!(b.paragraph_end(0), 2);
+        assert_eq!(b.paragraph_end(2), 6);
+        assert_eq!(b.paragraph_end(6), 7);
+        assert_eq!(b.paragraph_end(7), 7);
+        assert_eq!(b.paragraph_start(7), 6);
+        assert_eq!(b.paragraph_start(6), 3);
+        assert_eq!(b.paragraph_start(3), 0);
+        assert_eq!(b.paragraph_start(0), 0);
+        assert_eq!(b.first_non_blank(3), 2);
+        assert_eq!(b.first_non_blank(4), 0);
+    }
+
+    #[test]
+    fn test_write_to() {
+        let b = Buffer::from_str("ab\r\ncd\n");
+        let mut out = Vec::new();
+        b.write_to(&mut out).unwrap();
+        assert_eq!(out, b"ab\r\ncd\n");
+    }
+
+    #[test]
+    fn test_open_save() {
+        let dir = std::env::temp_dir()
+            .join(format!("rk-test-open-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("new.txt");
+
+        let mut b = Buffer::open(&path).unwrap();
+        assert_eq!(b.name, "new.txt");
+        assert_eq!(b.len_lines(), 1);
+        b.insert(0, 0, "hello\n");
+        b.modified = true;
+        b.save().unwrap();
+        assert!(!b.modified);
+
+        let b = Buffer::open(&path).unwrap();
+        assert_eq!(b.line(0), "hello");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::clipboard::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64("ł\n".as_bytes()), "xYIK");
+    }
+
+    #[test]
+    fn test_newlines() {
+        let pasted = newlines("a\r\nb\rc");
+        assert_eq!(pasted, "a\nb\nc");
+        let b = Buffer::from_str(&pasted);
+        assert_eq!(b.len_lines(), 3);
+        assert_eq!(b.line(2), "c");
+    }
+
+    #[test]
+    fn test_osc52() {
+        let mut out = Vec::new();
+        Clipboard::Osc52.copy("hi", &mut out).unwrap();
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07");
+        assert!(Clipboard::Osc52.paste().is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Clipboard::parse("osc52"), Some(Clipboard::Osc52));
+        assert_eq!(Clipboard::parse("xclip"), Some(Clipboard::Xclip));
+        assert_eq!(Clipboard::parse("none"), Some(Clipboard::None));
+        assert_eq!(Clipboard::parse("clippy"), None);
+    }
+}
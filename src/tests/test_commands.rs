@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use crate::commands::*;
+    use crate::display::LineNumbers;
+    use crate::editor::{Command, Direction};
+
+    fn all() -> Vec<Command> {
+        vec![
+            Command::Nothing,
+            Command::InsertCharacter('x'),
+            Command::InsertCharacter('"'),
+            Command::InsertCharacter('\\'),
+            Command::InsertCharacter('\n'),
+            Command::InsertCharacter('\x1b'),
+            Command::InsertCharacter(' '),
+            Command::InsertCharacter('λ'),
+            Command::InsertText("two\n\tlines".into()),
+            Command::InsertTab,
+            Command::Move(Direction::Up),
+            Command::Move(Direction::Down),
+            Command::MoveTo(10, 4),
+            Command::Click(0, 23),
+            Command::MovePageUp,
+            Command::MovePageDown,
+            Command::MoveLineHome,
+            Command::MoveLineEnd,
+            Command::MoveBufferStart,
+            Command::MoveBufferEnd,
+            Command::MoveWord(Direction::Right),
+            Command::MoveParagraph(Direction::Up),
+            Command::MoveFirstNonBlank,
+            Command::Erase(Direction::Left),
+            Command::Erase(Direction::Right),
+            Command::EraseWord(Direction::Left),
+            Command::KillLine,
+            Command::KillWord(Direction::Left),
+            Command::Yank,
+            Command::YankPop,
+            Command::SetMark,
+            Command::Select(Direction::Down),
+            Command::Cancel,
+            Command::DeleteRegion,
+            Command::CopyRegion,
+            Command::CutRegion,
+            Command::IndentRegion,
+            Command::DedentRegion,
+            Command::GotoLine(42),
+            Command::Save,
+            Command::SaveAs("/tmp/a file.txt".into()),
+            Command::Open("~/notes".into()),
+            Command::NextBuffer,
+            Command::PrevBuffer,
+            Command::SwitchBuffer("main.rs<2>".into()),
+            Command::CloseBuffer,
+            Command::ListBuffers,
+            Command::SplitHorizontal,
+            Command::SplitVertical,
+            Command::CloseWindow,
+            Command::CloseOtherWindows,
+            Command::NextWindow,
+            Command::FocusWindow(Direction::Left),
+            Command::FocusWindow(Direction::Right),
+            Command::GrowWindow,
+            Command::ShrinkWindow,
+            Command::ToggleWhitespace,
+            Command::LineNumbers(LineNumbers::Relative),
+            Command::LineNumbers(LineNumbers::Off),
+            Command::Undo,
+            Command::Redo,
+            Command::SearchForward,
+            Command::SearchBackward,
+            Command::SearchRegexForward,
+            Command::SearchRegexBackward,
+            Command::QueryReplace,
+            Command::ChooseLine,
+            Command::ChooseFile,
+            Command::ChooseSaveAs,
+            Command::ChooseBuffer,
+            Command::DescribeKey,
+            Command::Execute,
+            Command::Panic("forced \"panic\"".into()),
+            Command::Exit,
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for cmd in all() {
+            assert_eq!(parse(&display(&cmd)), Some(cmd.clone()), "{:?}", cmd);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(display(&Command::Move(Direction::Up)), "move up");
+        assert_eq!(display(&Command::MoveTo(10, 4)), "move-to 10 4");
+        assert_eq!(display(&Command::InsertCharacter('x')), "insert \"x\"");
+        assert_eq!(display(&Command::InsertCharacter('\n')), "insert \"\\n\"");
+        assert_eq!(
+            display(&Command::InsertCharacter('\x1b')),
+            "insert \"\\u{1b}\""
+        );
+        assert_eq!(
+            display(&Command::SwitchBuffer("a b".into())),
+            "switch-buffer \"a b\""
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("  save  "), Some(Command::Save));
+        assert_eq!(parse("insert x"), Some(Command::InsertCharacter('x')));
+        assert_eq!(
+            parse("insert \"\\u{3bb}\""),
+            Some(Command::InsertCharacter('λ'))
+        );
+        assert_eq!(
+            parse("open foo.txt"),
+            Some(Command::Open("foo.txt".into()))
+        );
+        assert_eq!(
+            parse("switch-buffer \"a b\""),
+            Some(Command::SwitchBuffer("a b".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_bad() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("quit"), None);
+        assert_eq!(parse("save now"), None);
+        assert_eq!(parse("move"), None);
+        assert_eq!(parse("move sideways"), None);
+        assert_eq!(parse("move-to 10"), None);
+        assert_eq!(parse("move-to 10 -4"), None);
+        assert_eq!(parse("insert xy"), None);
+        assert_eq!(parse("insert \"\""), None);
+        assert_eq!(parse("insert \"x"), None);
+        assert_eq!(parse("insert \"x\"y"), None);
+        assert_eq!(parse("insert \"\\q\""), None);
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+    use crate::display::LineNumbers;
+    use crate::editor::{Command, Direction};
+    use crate::keys;
+
+    #[test]
+    fn test_parse_config() {
+        let text = "# movement\n\nc-p move up\n  c-n   move   down  \nc-z nothing\nf5 save\n";
+        assert_eq!(
+            parse("config", text).unwrap().bindings,
+            vec![
+                (keys::must_parse_seq("c-p"), Command::Move(Direction::Up)),
+                (keys::must_parse_seq("c-n"), Command::Move(Direction::Down)),
+                (keys::must_parse_seq("c-z"), Command::Nothing),
+                (keys::must_parse_seq("f5"), Command::Save),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let err = |text| parse("rc", text).unwrap_err().to_string();
+        assert_eq!(err("c-s save\nx-q exit"), "rc:2: bad key \"x-q\"");
+        assert_eq!(err("c-q"), "rc:1: no command for key \"c-q\"");
+        assert_eq!(err("\nc-q quit"), "rc:2: unknown command \"quit\"");
+        assert_eq!(
+            err("c-q move sideways"),
+            "rc:1: unknown command \"move sideways\""
+        );
+    }
+
+    #[test]
+    fn test_parse_config_chords() {
+        let text = "c-x c-s save\nc-x   k close-buffer\nc-i insert x\n";
+        assert_eq!(
+            parse("config", text).unwrap().bindings,
+            vec![
+                (keys::must_parse_seq("c-x c-s"), Command::Save),
+                (keys::must_parse_seq("c-x k"), Command::CloseBuffer),
+                (keys::must_parse_seq("c-i"), Command::InsertCharacter('x')),
+            ]
+        );
+        let err = |text| parse("rc", text).unwrap_err().to_string();
+        assert_eq!(err("c-x c-q quit"), "rc:1: unknown command \"quit\"");
+        assert_eq!(err("c-x c-s"), "rc:1: no command for key \"c-x c-s\"");
+    }
+
+    #[test]
+    fn test_parse_config_keymap() {
+        let text = "keymap vi\nclipboard xclip\nc-s save\n";
+        let config = parse("config", text).unwrap();
+        assert_eq!(config.keymap, Some("vi".into()));
+        assert_eq!(config.clipboard, Some("xclip".into()));
+        assert_eq!(config.bindings.len(), 1);
+        assert_eq!(parse("config", "").unwrap().keymap, None);
+    }
+
+    #[test]
+    fn test_parse_config_tabs() {
+        let config = parse("config", "tab-width 8\nexpand-tab no\n").unwrap();
+        assert_eq!(config.tab_width, Some(8));
+        assert_eq!(config.expand_tab, Some(false));
+        let err = |text| parse("rc", text).unwrap_err().to_string();
+        assert_eq!(err("tab-width 0"), "rc:1: bad tab width \"0\"");
+        assert_eq!(err("expand-tab maybe"), "rc:1: expected yes or no");
+    }
+
+    #[test]
+    fn test_parse_config_line_numbers() {
+        let config = parse("config", "line-numbers hybrid\n").unwrap();
+        assert_eq!(config.line_numbers, Some(LineNumbers::Hybrid));
+        let err = parse("rc", "line-numbers some").unwrap_err().to_string();
+        assert_eq!(err, "rc:1: unknown line numbers \"some\"");
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let path = std::env::temp_dir().join("rk-no-such-config");
+        assert_eq!(load(&path).unwrap(), Config::default());
+    }
+}
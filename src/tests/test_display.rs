@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::display::*;
+
+    #[test]
+    fn test_glyphs() {
+        // CJK is double width, a combining accent joins the e, and a ZWJ
+        // emoji sequence is a single double-width glyph
+        let line = "a\u{4e2d}e\u{301}\u{1f468}\u{200d}\u{1f469}b";
+        let glyphs: Vec<(usize, usize, usize)> = glyphs(line, 4, usize::MAX)
+            .map(|g| (g.idx, g.col, g.width))
+            .collect();
+        assert_eq!(
+            glyphs,
+            vec![(0, 0, 1), (1, 1, 2), (2, 3, 1), (4, 4, 2), (7, 6, 1)]
+        );
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let glyphs: Vec<Glyph> = glyphs("\u{200b}x", 4, usize::MAX).collect();
+        assert_eq!(glyphs[0].width, 8);
+        assert_eq!(glyphs[0].face, Face::Control);
+        assert_eq!(glyphs[1].col, 8);
+    }
+
+    #[test]
+    fn test_tabs() {
+        let glyphs: Vec<(usize, usize)> = glyphs("\tab\tc", 4, usize::MAX)
+            .map(|g| (g.col, g.width))
+            .collect();
+        assert_eq!(glyphs, vec![(0, 4), (4, 1), (5, 1), (6, 2), (8, 1)]);
+        assert_eq!(col("ab\tc", 3, 8), 8);
+        assert_eq!(idx("ab\tc", 5, 8), 2);
+        assert_eq!(idx("ab\tc", 8, 8), 3);
+    }
+
+    #[test]
+    fn test_col_idx() {
+        let line = "a\u{4e2d}e\u{301}b";
+        assert_eq!(col(line, 0, 4), 0);
+        assert_eq!(col(line, 2, 4), 3);
+        assert_eq!(col(line, 4, 4), 4);
+        assert_eq!(col(line, 5, 4), 5);
+        assert_eq!(col("", 3, 4), 0);
+        assert_eq!(idx(line, 0, 4), 0);
+        assert_eq!(idx(line, 1, 4), 1);
+        assert_eq!(idx(line, 2, 4), 1);
+        assert_eq!(idx(line, 3, 4), 2);
+        assert_eq!(idx(line, 4, 4), 4);
+        assert_eq!(idx(line, 9, 4), 5);
+    }
+
+    #[test]
+    fn test_control() {
+        let shown: Vec<(String, usize, Face)> =
+            glyphs("a\r\x1b\0\x7f", 4, usize::MAX)
+                .map(|g| (g.text, g.col, g.face))
+                .collect();
+        assert_eq!(
+            shown,
+            vec![
+                ("a".into(), 0, Face::Normal),
+                ("^M".into(), 1, Face::Control),
+                ("^[".into(), 3, Face::Control),
+                ("^@".into(), 5, Face::Control),
+                ("^?".into(), 7, Face::Control),
+            ]
+        );
+        let g: Vec<Glyph> = glyphs("\u{85}\u{200b}", 4, usize::MAX).collect();
+        assert_eq!(g[0].text, "<U+0085>");
+        assert_eq!(g[1].text, "<U+200B>");
+        assert_eq!(g[1].col, 8);
+        assert_eq!(idx("\u{200b}x", 8, 4), 1);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let faces: Vec<Face> =
+            glyphs("a b\t ", 4, 3).map(|g| g.face).collect();
+        assert_eq!(
+            faces,
+            vec![
+                Face::Normal,
+                Face::Normal,
+                Face::Normal,
+                Face::Whitespace,
+                Face::Whitespace,
+            ]
+        );
+        let markers: Vec<String> =
+            glyphs("a\t ", 4, 1).map(|g| g.marker()).collect();
+        assert_eq!(markers, vec!["a", "\u{bb}  ", "\u{b7}"]);
+
+        // A long line is laid out only as far as the window shows, and its
+        // inner spaces aren't trailing because of that
+        let b = Buffer::from_str(&format!("a b {} ", "x".repeat(100)));
+        let trailing = b.trailing_whitespace(0);
+        assert_eq!(trailing, 104);
+        let faces: Vec<Face> = glyphs(&b.line_slice(0, 0, 4), 4, trailing)
+            .map(|g| g.face)
+            .collect();
+        assert_eq!(faces, vec![Face::Normal; 4]);
+        let last = glyphs(&b.line(0), 4, trailing).last().unwrap();
+        assert_eq!((last.idx, last.face), (104, Face::Whitespace));
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        assert_eq!(LineNumbers::Absolute.number(4, 9), 5);
+        assert_eq!(LineNumbers::Relative.number(4, 9), 5);
+        assert_eq!(LineNumbers::Relative.number(9, 9), 0);
+        assert_eq!(LineNumbers::Relative.number(12, 9), 3);
+        assert_eq!(LineNumbers::Hybrid.number(9, 9), 10);
+        assert_eq!(LineNumbers::Hybrid.number(8, 9), 1);
+        assert_eq!(LineNumbers::Off.gutter_width(5000), 0);
+        assert_eq!(LineNumbers::Absolute.gutter_width(5), 4);
+        assert_eq!(LineNumbers::Relative.gutter_width(12345), 6);
+        assert_eq!(LineNumbers::parse("hybrid"), Some(LineNumbers::Hybrid));
+        assert_eq!(LineNumbers::parse("on"), None);
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::*;
+
+    fn text(b: &Buffer) -> String {
+        let mut out = Vec::new();
+        b.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut b = Buffer::from_str("ab\ncd");
+        b.begin_edit((1, 0));
+        b.insert(1, 0, "x");
+        b.end_edit((2, 0));
+        b.seal();
+        b.begin_edit((1, 1));
+        b.remove((0, 1), (1, 1));
+        b.end_edit((0, 1));
+        b.seal();
+        assert_eq!(text(&b), "axb\nd");
+
+        assert_eq!(b.undo(), Some((1, 1)));
+        assert_eq!(text(&b), "axb\ncd");
+        assert_eq!(b.undo(), Some((1, 0)));
+        assert_eq!(text(&b), "ab\ncd");
+        assert_eq!(b.undo(), None);
+
+        assert_eq!(b.redo(), Some((2, 0)));
+        assert_eq!(text(&b), "axb\ncd");
+        assert_eq!(b.redo(), Some((0, 1)));
+        assert_eq!(text(&b), "axb\nd");
+        assert_eq!(b.redo(), None);
+    }
+
+    #[test]
+    fn test_group() {
+        let mut b = Buffer::new();
+        for (x, ch) in "abc".chars().enumerate() {
+            b.begin_edit((x, 0));
+            b.insert(x, 0, &ch.to_string());
+            b.end_edit((x + 1, 0));
+        }
+        b.seal();
+        b.begin_edit((3, 0));
+        b.insert(3, 0, "\n");
+        b.end_edit((0, 1));
+        assert_eq!(text(&b), "abc\n");
+
+        assert_eq!(b.undo(), Some((3, 0)));
+        assert_eq!(text(&b), "abc");
+        assert_eq!(b.undo(), Some((0, 0)));
+        assert_eq!(text(&b), "");
+    }
+
+    #[test]
+    fn test_edit_clears_redo() {
+        let mut b = Buffer::from_str("a");
+        b.insert(1, 0, "b");
+        b.seal();
+        assert_eq!(b.undo(), Some((1, 0)));
+        b.insert(1, 0, "c");
+        b.seal();
+        assert_eq!(b.redo(), None);
+        assert_eq!(text(&b), "ac");
+    }
+
+    #[test]
+    fn test_empty_step() {
+        let mut b = Buffer::from_str("a");
+        b.begin_edit((0, 0));
+        b.end_edit((0, 0));
+        b.seal();
+        assert_eq!(b.undo(), None);
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod tests {
+    use crate::editor::Command;
+    use crate::keymap::*;
+    use crate::keys::must_parse_seq as seq;
+
+    #[test]
+    fn test_lookup() {
+        let mut km = Keymap::new();
+        km.bind(seq("c-s"), Command::Save);
+        km.bind(seq("c-x c-s"), Command::Save);
+        km.bind(seq("c-x c-c"), Command::Exit);
+        assert_eq!(km.lookup(&seq("c-s")), Lookup::Command(&Command::Save));
+        assert_eq!(km.lookup(&seq("c-x")), Lookup::Prefix);
+        assert_eq!(
+            km.lookup(&seq("c-x c-c")),
+            Lookup::Command(&Command::Exit)
+        );
+        assert_eq!(km.lookup(&seq("c-x c-x")), Lookup::Unbound);
+        assert_eq!(km.lookup(&seq("c-s c-s")), Lookup::Unbound);
+        assert_eq!(km.lookup(&seq("c-q")), Lookup::Unbound);
+        assert_eq!(km.lookup(&[]), Lookup::Unbound);
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut km = Keymap::new();
+        km.bind(seq("c-x"), Command::Undo);
+        km.bind(seq("c-x u"), Command::Undo);
+        assert_eq!(km.lookup(&seq("c-x")), Lookup::Prefix);
+        assert_eq!(km.lookup(&seq("c-x u")), Lookup::Command(&Command::Undo));
+        km.bind(seq("c-x"), Command::Redo);
+        assert_eq!(km.lookup(&seq("c-x")), Lookup::Command(&Command::Redo));
+        assert_eq!(km.lookup(&seq("c-x u")), Lookup::Unbound);
+    }
+}
@@ -7,31 +7,19 @@ mod tests {
     fn test_parse_nomod() {
         assert_eq!(
             parse("c"),
-            Some(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::empty()))
         );
         assert_eq!(
             parse("g"),
-            Some(KeyEvent {
-                code: KeyCode::Char('g'),
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Char('g'), KeyModifiers::empty()))
         );
         assert_eq!(
             parse("h"),
-            Some(KeyEvent {
-                code: KeyCode::Char('h'),
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Char('h'), KeyModifiers::empty()))
         );
         assert_eq!(
             parse("a"),
-            Some(KeyEvent {
-                code: KeyCode::Char('a'),
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::empty()))
         );
     }
 
@@ -39,31 +27,19 @@ mod tests {
     fn test_parse_one_mod() {
         assert_eq!(
             parse("C-c"),
-            Some(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
-            })
+            Some(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL))
         );
         assert_eq!(
             parse("C-a"),
-            Some(KeyEvent {
-                code: KeyCode::Char('a'),
-                modifiers: KeyModifiers::CONTROL,
-            })
+            Some(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL))
         );
         assert_eq!(
             parse("c-a"),
-            Some(KeyEvent {
-                code: KeyCode::Char('a'),
-                modifiers: KeyModifiers::CONTROL,
-            })
+            Some(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL))
         );
         assert_eq!(
             parse("a-C"),
-            Some(KeyEvent {
-                code: KeyCode::Char('C'),
-                modifiers: KeyModifiers::ALT,
-            })
+            Some(KeyEvent::new(KeyCode::Char('C'), KeyModifiers::ALT))
         );
     }
 
@@ -71,17 +47,17 @@ mod tests {
     fn test_parse_many_mods() {
         assert_eq!(
             parse("C-a-c"),
-            Some(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
-            })
+            Some(KeyEvent::new(
+                KeyCode::Char('c'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
         );
         assert_eq!(
             parse("c-a-q"),
-            Some(KeyEvent {
-                code: KeyCode::Char('q'),
-                modifiers: KeyModifiers::CONTROL | KeyModifiers::ALT,
-            })
+            Some(KeyEvent::new(
+                KeyCode::Char('q'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
         );
     }
 
@@ -89,24 +65,48 @@ mod tests {
     fn test_parse_nonalnum() {
         assert_eq!(
             parse("enter"),
-            Some(KeyEvent {
-                code: KeyCode::Enter,
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Enter, KeyModifiers::empty()))
         );
         assert_eq!(
             parse("Backspace"),
-            Some(KeyEvent {
-                code: KeyCode::Backspace,
-                modifiers: KeyModifiers::empty(),
-            })
+            Some(KeyEvent::new(KeyCode::Backspace, KeyModifiers::empty()))
         );
         assert_eq!(
             parse("c-f3"),
-            Some(KeyEvent {
-                code: KeyCode::F(3),
-                modifiers: KeyModifiers::CONTROL,
-            })
+            Some(KeyEvent::new(KeyCode::F(3), KeyModifiers::CONTROL))
         );
     }
+
+    #[test]
+    fn test_parse_space() {
+        // Terminals send c-space as a NUL byte, which crossterm reads as
+        // this
+        let cspace = KeyEvent::new(KeyCode::Char(' '), KeyModifiers::CONTROL);
+        assert_eq!(parse("c-space"), Some(cspace));
+        assert_eq!(display(cspace), "C-space");
+        let null = KeyEvent::new(KeyCode::Null, KeyModifiers::empty());
+        assert_eq!(parse("null"), Some(null));
+        assert_eq!(display(null), "null");
+        assert_eq!(display(must_parse("space")), "space");
+        assert_eq!(parse("space").map(|k| k.code), Some(KeyCode::Char(' ')));
+    }
+
+    #[test]
+    fn test_parse_bad() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("c-"), None);
+        assert_eq!(parse("x-q"), None);
+        assert_eq!(parse("c-nosuchkey"), None);
+    }
+
+    #[test]
+    fn test_parse_seq() {
+        let cx = parse("c-x").unwrap();
+        let cs = parse("c-s").unwrap();
+        assert_eq!(parse_seq("c-x"), Some(vec![cx]));
+        assert_eq!(parse_seq(" c-x  c-s "), Some(vec![cx, cs]));
+        assert_eq!(parse_seq(""), None);
+        assert_eq!(parse_seq("c-x nosuchkey"), None);
+        assert_eq!(display_seq(&[cx, cs]), "C-x C-s");
+    }
 }
@@ -0,0 +1,41 @@
+#[cfg(test)]
+mod tests {
+    use crate::killring::*;
+
+    #[test]
+    fn test_yank() {
+        let mut r = KillRing::new(10);
+        assert_eq!(r.yank(), None);
+        assert_eq!(r.yank_pop(), None);
+        r.push("one".into());
+        r.push("two".into());
+        r.push("three".into());
+        assert_eq!(r.yank(), Some("three"));
+        assert_eq!(r.yank_pop(), Some("two"));
+        assert_eq!(r.yank_pop(), Some("one"));
+        assert_eq!(r.yank_pop(), Some("three"));
+        assert_eq!(r.yank_pop(), Some("two"));
+        assert_eq!(r.yank(), Some("three"));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut r = KillRing::new(10);
+        r.append("b", false);
+        r.append("c", false);
+        r.append("a", true);
+        assert_eq!(r.yank(), Some("abc"));
+        assert_eq!(r.yank_pop(), Some("abc"));
+    }
+
+    #[test]
+    fn test_cap() {
+        let mut r = KillRing::new(2);
+        r.push("one".into());
+        r.push("two".into());
+        r.push("three".into());
+        assert_eq!(r.yank(), Some("three"));
+        assert_eq!(r.yank_pop(), Some("two"));
+        assert_eq!(r.yank_pop(), Some("three"));
+    }
+}
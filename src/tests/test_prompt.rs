@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::keys;
+    use crate::prompt::*;
+
+    fn none(_: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn feed(p: &mut Prompt, ks: &[&str], history: &[String]) -> Outcome {
+        let mut outcome = Outcome::Pending;
+        for k in ks {
+            outcome = p.key(keys::must_parse(k), history, &none);
+        }
+        outcome
+    }
+
+    #[test]
+    fn test_prompt_editing() {
+        let mut p = Prompt::new(PromptKind::Line, "Go to line: ");
+        feed(&mut p, &["a", "c", "left", "b"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("abc", 2));
+        feed(&mut p, &["c-a", "delete", "end", "backspace"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("b", 1));
+        feed(&mut p, &["x", "y", "left", "c-k"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("bx", 2));
+        feed(&mut p, &["left", "c-u"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("x", 0));
+        assert_eq!(feed(&mut p, &["enter"], &[]), Outcome::Done("x".into()));
+        // An accented e is one grapheme, moved over and erased whole
+        p.set_text("e\u{301}e\u{301}");
+        feed(&mut p, &["left", "backspace"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("e\u{301}", 0));
+        feed(&mut p, &["delete"], &[]);
+        assert_eq!((p.text.as_str(), p.pos), ("", 0));
+    }
+
+    #[test]
+    fn test_prompt_outcome() {
+        let mut p = Prompt::new(PromptKind::Line, "");
+        assert_eq!(feed(&mut p, &["esc"], &[]), Outcome::Cancelled);
+        assert_eq!(feed(&mut p, &["c-g"], &[]), Outcome::Cancelled);
+        assert_eq!(feed(&mut p, &["backspace"], &[]), Outcome::Pending);
+        assert_eq!(feed(&mut p, &["c-q"], &[]), Outcome::Ignored);
+        assert_eq!(feed(&mut p, &["a-x"], &[]), Outcome::Ignored);
+        assert_eq!(p.text, "");
+    }
+
+    #[test]
+    fn test_prompt_history() {
+        let history = vec!["one".to_string(), "two".to_string()];
+        let mut p = Prompt::new(PromptKind::Search, "");
+        feed(&mut p, &["x"], &history);
+        feed(&mut p, &["up"], &history);
+        assert_eq!(p.text, "two");
+        feed(&mut p, &["up", "up"], &history);
+        assert_eq!(p.text, "one");
+        feed(&mut p, &["down"], &history);
+        assert_eq!(p.text, "two");
+        feed(&mut p, &["down"], &history);
+        assert_eq!((p.text.as_str(), p.pos), ("x", 1));
+    }
+
+    #[test]
+    fn test_prompt_complete() {
+        let names = |text: &str| -> Vec<String> {
+            ["main.rs", "mod.rs", "mode.rs"]
+                .iter()
+                .filter(|n| n.starts_with(text))
+                .map(|n| n.to_string())
+                .collect()
+        };
+        let tab = keys::must_parse("tab");
+        let mut p = Prompt::new(PromptKind::Buffer, "");
+        p.key(keys::must_parse("m"), &[], &names);
+        p.key(tab, &[], &names);
+        assert_eq!(p.text, "m");
+        assert_eq!(p.hint, " {main.rs | mod.rs | mode.rs}");
+        p.key(keys::must_parse("o"), &[], &names);
+        p.key(tab, &[], &names);
+        assert_eq!((p.text.as_str(), p.hint.as_str()), ("mod", ""));
+        p.key(keys::must_parse("e"), &[], &names);
+        p.key(tab, &[], &names);
+        assert_eq!((p.text.as_str(), p.pos), ("mode.rs", 7));
+        p.key(keys::must_parse("x"), &[], &names);
+        p.key(tab, &[], &names);
+        assert_eq!(p.hint, " [no match]");
+    }
+
+    #[test]
+    fn test_prompt_render() {
+        let mut p = Prompt::new(PromptKind::File, "> ");
+        p.set_text("abcdef");
+        assert_eq!(p.render(20), ("> abcdef".to_string(), 8));
+        assert_eq!(p.render(6), ("bcdef".to_string(), 5));
+        feed(&mut p, &["home"], &[]);
+        assert_eq!(p.render(6), ("> abcd".to_string(), 2));
+        // Wide text is measured in cells, and never cut in half
+        p.set_text("\u{4e2d}\u{6587}\u{5b57}");
+        assert_eq!(p.render(20), ("> \u{4e2d}\u{6587}\u{5b57}".into(), 8));
+        assert_eq!(p.render(5), ("\u{6587}\u{5b57}".into(), 4));
+        assert_eq!(p.render(4), ("\u{5b57}".into(), 2));
+        p.pos = 1;
+        assert_eq!(p.render(6), ("> \u{4e2d}\u{6587}".into(), 4));
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let v = |s: &[&str]| -> Vec<String> {
+            s.iter().map(|s| s.to_string()).collect()
+        };
+        assert_eq!(common_prefix(&v(&[])), "");
+        assert_eq!(common_prefix(&v(&["abc"])), "abc");
+        assert_eq!(common_prefix(&v(&["abc", "abd", "ab"])), "ab");
+        assert_eq!(common_prefix(&v(&["αβγ", "αβδ"])), "αβ");
+        assert_eq!(common_prefix(&v(&["x", "y"])), "");
+    }
+
+    #[test]
+    fn test_remember() {
+        let mut h = Vec::new();
+        remember(&mut h, "a");
+        remember(&mut h, "a");
+        remember(&mut h, "");
+        remember(&mut h, "b");
+        assert_eq!(h, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let dir =
+            std::env::temp_dir().join(format!("rk-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("file"), "").unwrap();
+        std::fs::write(dir.join(".hidden"), "").unwrap();
+        let d = format!("{}/", dir.display());
+        assert_eq!(
+            complete_path(&d),
+            vec![format!("{}file", d), format!("{}sub/", d)]
+        );
+        assert_eq!(
+            complete_path(&format!("{}s", d)),
+            vec![format!("{}sub/", d)]
+        );
+        assert_eq!(
+            complete_path(&format!("{}.h", d)),
+            vec![format!("{}.hidden", d)]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::display::Face;
+    use crate::screen::*;
+
+    fn flush(screen: &mut Screen, cursor: (usize, usize)) -> String {
+        let mut out = Vec::new();
+        screen.flush(&mut out, cursor).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The text in out, without the escape sequences.
+    fn printed(out: &str) -> String {
+        let mut text = String::new();
+        let mut chars = out.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.find(|c| c.is_ascii_alphabetic());
+            } else {
+                text.push(c);
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_first_flush() {
+        let mut screen = Screen::new(4, 2);
+        screen.print(0, 0, "abc", 4, Face::Normal);
+        let out = flush(&mut screen, (0, 0));
+        assert!(out.contains("\x1b[2J"));
+        assert_eq!(printed(&out), "abc     ");
+    }
+
+    #[test]
+    fn test_only_changes() {
+        let mut screen = Screen::new(4, 2);
+        screen.print(0, 0, "abc", 4, Face::Normal);
+        flush(&mut screen, (0, 0));
+        // Nothing changed, and the cursor stayed put
+        screen.print(0, 0, "abc", 4, Face::Normal);
+        assert_eq!(flush(&mut screen, (0, 0)), "");
+        // Only the cell that changed is sent
+        screen.print(0, 0, "abd", 4, Face::Normal);
+        let out = flush(&mut screen, (0, 0));
+        assert!(out.contains("\x1b[1;3H"));
+        assert_eq!(printed(&out), "d");
+        assert!(!out.contains("\x1b[2J"));
+        // Moving the cursor alone
+        assert_eq!(flush(&mut screen, (1, 1)), "\x1b[2;2H\x1b[?25h");
+    }
+
+    #[test]
+    fn test_wide() {
+        let mut screen = Screen::new(4, 1);
+        screen.print(0, 0, "\u{4e2d}ab", 4, Face::Normal);
+        flush(&mut screen, (0, 0));
+        screen.print(0, 0, "\u{4e2d}ax", 4, Face::Normal);
+        let out = flush(&mut screen, (0, 0));
+        assert!(out.contains("\x1b[1;4H"));
+        assert_eq!(printed(&out), "x");
+        // A wide glyph doesn't fit in the last cell
+        screen.print(0, 0, "abc\u{4e2d}", 4, Face::Normal);
+        let out = flush(&mut screen, (0, 0));
+        assert_eq!(printed(&out), "abc ");
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut screen = Screen::new(4, 1);
+        flush(&mut screen, (0, 0));
+        screen.resize(4, 1);
+        assert_eq!(flush(&mut screen, (0, 0)), "");
+        screen.resize(5, 1);
+        assert!(flush(&mut screen, (0, 0)).contains("\x1b[2J"));
+    }
+}
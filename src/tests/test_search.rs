@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::search::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(matches("", "a"), vec![]);
+        assert_eq!(matches("abc", ""), vec![]);
+        assert_eq!(matches("abcabc", "bc"), vec![(1, 2), (4, 2)]);
+        assert_eq!(matches("aaaa", "aa"), vec![(0, 2), (2, 2)]);
+        assert_eq!(matches("καλημέρα", "μέ"), vec![(4, 2)]);
+    }
+
+    #[test]
+    fn test_matches_case() {
+        assert_eq!(matches("Foo foo", "foo"), vec![(0, 3), (4, 3)]);
+        assert_eq!(matches("Foo foo", "Foo"), vec![(0, 3)]);
+        assert_eq!(matches("ΚΑΛΗ", "καλη"), vec![(0, 4)]);
+    }
+
+    #[test]
+    fn test_find() {
+        let b = Buffer::from_str("ab\nxab\nab");
+        let mut h = Haystack::default();
+        let ab = Pattern::new("ab", false).unwrap();
+        let zz = Pattern::new("zz", false).unwrap();
+        let x = Pattern::new("x", false).unwrap();
+        assert_eq!(find(&b, &ab, 0, true, &mut h), Some((0, false)));
+        assert_eq!(find(&b, &ab, 1, true, &mut h), Some((4, false)));
+        assert_eq!(find(&b, &ab, 5, true, &mut h), Some((7, false)));
+        assert_eq!(find(&b, &ab, 8, true, &mut h), Some((0, true)));
+        assert_eq!(find(&b, &zz, 0, true, &mut h), None);
+
+        assert_eq!(find(&b, &ab, 9, false, &mut h), Some((7, false)));
+        assert_eq!(find(&b, &ab, 6, false, &mut h), Some((4, false)));
+        assert_eq!(find(&b, &ab, 3, false, &mut h), Some((0, false)));
+        assert_eq!(find(&b, &x, 2, false, &mut h), Some((3, true)));
+    }
+
+    #[test]
+    fn test_find_regex() {
+        let b = Buffer::from_str("foo1\nbar22\nfoo3");
+        let mut h = Haystack::default();
+        let digits = Pattern::new("[0-9]+", true).unwrap();
+        assert_eq!(digits.matches("bar22x3"), vec![(3, 2), (6, 1)]);
+        assert_eq!(find(&b, &digits, 4, true, &mut h), Some((8, false)));
+        assert_eq!(find(&b, &digits, 7, false, &mut h), Some((3, false)));
+
+        let across = Pattern::new("1\nb", true).unwrap();
+        assert_eq!(find(&b, &across, 0, true, &mut h), Some((3, false)));
+        assert_eq!(find(&b, &across, 4, true, &mut h), Some((3, true)));
+
+        // Classes that include a line ending match across lines too
+        let space = Pattern::new("[0-9]\\s+[a-z]", true).unwrap();
+        assert_eq!(find(&b, &space, 0, true, &mut h), Some((3, false)));
+        let not_x = Pattern::new("2[^x]f", true).unwrap();
+        assert_eq!(find(&b, &not_x, 0, true, &mut h), Some((9, false)));
+        let dot = Pattern::new("2.f", true).unwrap();
+        assert_eq!(find(&b, &dot, 0, true, &mut h), None);
+
+        assert!(Pattern::new("(", true).is_err());
+    }
+
+    #[test]
+    fn test_find_replace() {
+        let b = Buffer::from_str("a=1\nbb=22");
+        let mut h = Haystack::default();
+        let re = Regex::new("([a-z]+)=([0-9]+)").unwrap();
+        assert_eq!(
+            find_replace(&b, &re, "$2=$1", 0, None, &mut h),
+            Some(Found {
+                start: 0,
+                end: 3,
+                replacement: "1=a".into()
+            })
+        );
+        assert_eq!(
+            find_replace(&b, &re, "${2}x", 1, None, &mut h),
+            Some(Found {
+                start: 4,
+                end: 9,
+                replacement: "22x".into()
+            })
+        );
+        assert_eq!(find_replace(&b, &re, "", 6, None, &mut h), None);
+
+        let re = Regex::new("1\\n(b)").unwrap();
+        assert_eq!(
+            find_replace(&b, &re, "-$1", 0, None, &mut h),
+            Some(Found {
+                start: 2,
+                end: 5,
+                replacement: "-b".into()
+            })
+        );
+
+        // The copy of the text keeps up with the replacements
+        let mut b = Buffer::from_str("1\nb 1\nb");
+        let mut h = Haystack::default();
+        let found = find_replace(&b, &re, "-$1", 0, None, &mut h).unwrap();
+        h.replace(
+            b.char_to_byte(found.start),
+            b.char_to_byte(found.end),
+            "-b",
+        );
+        b.remove((0, 0), (1, 1));
+        b.insert(0, 0, "-b");
+        assert_eq!(
+            find_replace(&b, &re, "-$1", 2, None, &mut h),
+            Some(Found {
+                start: 3,
+                end: 6,
+                replacement: "-b".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_replace_all_empty() {
+        // Replace every match the way query-replace's ! does
+        let replace_all = |text: &str, re: &str, with: &str| {
+            let mut b = Buffer::from_str(text);
+            let re = Regex::new(re).unwrap();
+            let mut h = Haystack::default();
+            let (mut from, mut prev_end) = (0, None);
+            for _ in 0..100 {
+                let found = match find_replace(
+                    &b, &re, with, from, prev_end, &mut h,
+                ) {
+                    Some(found) => found,
+                    None => return b.text(),
+                };
+                let start = b.char_to_pos(found.start);
+                b.remove(start, b.char_to_pos(found.end));
+                b.insert(start.0, start.1, &found.replacement);
+                from = found.start + found.replacement.chars().count();
+                prev_end = Some(from);
+            }
+            panic!("replacing {:?} doesn't end", re);
+        };
+        assert_eq!(replace_all("ab\r\ncd", "$", "!"), "ab!\r\ncd!");
+        assert_eq!(replace_all("ab\ncd", "(?m)^", ">"), ">ab\n>cd");
+        assert_eq!(replace_all("ab", "x*", "-"), "-a-b-");
+        assert_eq!(replace_all("aab", "a*", "-"), "-b-");
+    }
+}
@@ -30,4 +30,20 @@ mod tests {
         assert_eq!("καλημέρα".uslice(4, 6), "μέ");
         assert_eq!("καλημέρα".uslice(4, 8), "μέρα");
     }
+
+    #[test]
+    fn test_graphemes() {
+        // e and a combining accent, then a flag of two regional indicators
+        let s = "ae\u{301}\u{1f1ec}\u{1f1f7}b";
+        assert_eq!(s.next_grapheme(0), 1);
+        assert_eq!(s.next_grapheme(1), 3);
+        assert_eq!(s.next_grapheme(2), 3);
+        assert_eq!(s.next_grapheme(3), 5);
+        assert_eq!(s.next_grapheme(6), 6);
+        assert_eq!(s.prev_grapheme(5), 3);
+        assert_eq!(s.prev_grapheme(3), 1);
+        assert_eq!(s.prev_grapheme(2), 1);
+        assert_eq!(s.prev_grapheme(0), 0);
+        assert_eq!("".next_grapheme(0), 0);
+    }
 }
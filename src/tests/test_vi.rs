@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use crate::editor::Command;
+    use crate::keys;
+    use crate::vi::*;
+
+    fn feed(vi: &mut Vi, ks: &str) -> Step {
+        let mut step = Step::Invalid;
+        for k in keys::must_parse_seq(ks) {
+            step = vi.key(k);
+        }
+        step
+    }
+
+    fn done(a: Action) -> Step {
+        Step::Done(a)
+    }
+
+    #[test]
+    fn test_vi_keys() {
+        let mut vi = Vi::new();
+        assert_eq!(
+            feed(&mut vi, "w"),
+            done(Action::Move(Motion::WordForward(false), 0))
+        );
+        assert_eq!(
+            feed(&mut vi, "1 2 j"),
+            done(Action::Move(Motion::Down, 12))
+        );
+        assert_eq!(
+            feed(&mut vi, "d w"),
+            done(Action::Operate(
+                Operator::Delete,
+                Motion::WordForward(false),
+                0
+            ))
+        );
+        assert_eq!(
+            feed(&mut vi, "2 d 3 w"),
+            done(Action::Operate(
+                Operator::Delete,
+                Motion::WordForward(false),
+                6
+            ))
+        );
+        assert_eq!(
+            feed(&mut vi, "c $"),
+            done(Action::Operate(Operator::Change, Motion::LineEnd, 0))
+        );
+        assert_eq!(
+            feed(&mut vi, "y y"),
+            done(Action::Operate(Operator::Yank, Motion::Line, 0))
+        );
+        assert_eq!(
+            feed(&mut vi, "3 d d"),
+            done(Action::Operate(Operator::Delete, Motion::Line, 3))
+        );
+        assert_eq!(
+            feed(&mut vi, "g g"),
+            done(Action::Move(Motion::FirstLine, 0))
+        );
+        assert_eq!(
+            feed(&mut vi, "0"),
+            done(Action::Move(Motion::LineStart, 0))
+        );
+        assert_eq!(
+            feed(&mut vi, "1 0 G"),
+            done(Action::Move(Motion::GotoLine, 10))
+        );
+        assert_eq!(
+            feed(&mut vi, ":"),
+            done(Action::Command(Command::Execute))
+        );
+    }
+
+    #[test]
+    fn test_vi_typed() {
+        let mut vi = Vi::new();
+        assert_eq!(feed(&mut vi, "2 d"), Step::Wait);
+        assert_eq!(vi.typed, "2d");
+        assert_eq!(feed(&mut vi, "y"), Step::Invalid);
+        assert_eq!(vi.typed, "");
+        assert_eq!(feed(&mut vi, "d i"), Step::Invalid);
+        assert_eq!(feed(&mut vi, "c-s"), Step::Pass);
+        assert_eq!(feed(&mut vi, "d c-s"), Step::Invalid);
+    }
+
+    #[test]
+    fn test_vi_visual() {
+        let mut vi = Vi::new();
+        vi.mode = Mode::Visual;
+        assert_eq!(
+            feed(&mut vi, "d"),
+            done(Action::OperateSelection(Operator::Delete))
+        );
+        assert_eq!(
+            feed(&mut vi, "e"),
+            done(Action::Move(Motion::WordEnd(false), 0))
+        );
+        assert_eq!(feed(&mut vi, "esc"), done(Action::Mode(Mode::Normal)));
+    }
+
+    #[test]
+    fn test_target_words() {
+        let b = Buffer::from_str("foo.bar->x  baz\n\n  qux");
+        let w = |pos, n| target(&b, pos, Motion::WordForward(false), n);
+        assert_eq!(w((0, 0), 1), (7, 0));
+        assert_eq!(w((0, 0), 3), (12, 0));
+        assert_eq!(w((12, 0), 1), (0, 1));
+        assert_eq!(w((0, 1), 1), (2, 2));
+        assert_eq!(w((2, 2), 1), (5, 2));
+        let b_ = |pos, n| target(&b, pos, Motion::WordBackward(false), n);
+        assert_eq!(b_((2, 2), 1), (0, 1));
+        assert_eq!(b_((12, 0), 2), (7, 0));
+        assert_eq!(b_((0, 0), 1), (0, 0));
+        let e = |pos, n| target(&b, pos, Motion::WordEnd(false), n);
+        assert_eq!(e((0, 0), 1), (6, 0));
+        assert_eq!(e((6, 0), 1), (8, 0));
+        assert_eq!(e((12, 0), 1), (14, 0));
+        assert_eq!(e((14, 0), 1), (4, 2));
+        assert_eq!(e((4, 2), 1), (4, 2));
+
+        // WORDs run from blank to blank
+        let big = |m, pos| target(&b, pos, m, 1);
+        assert_eq!(big(Motion::WordForward(true), (0, 0)), (12, 0));
+        assert_eq!(big(Motion::WordBackward(true), (12, 0)), (0, 0));
+        assert_eq!(big(Motion::WordEnd(true), (0, 0)), (9, 0));
+
+        // The same words as the Emacs word motions: an apostrophe or a
+        // decimal point doesn't split one, and CJK ideographs are words
+        // on their own
+        let b = Buffer::from_str("can't 3.14 \u{4e2d}\u{6587}");
+        let w = |pos| target(&b, pos, Motion::WordForward(false), 1);
+        assert_eq!(w((0, 0)), (6, 0));
+        assert_eq!(w((6, 0)), (11, 0));
+        assert_eq!(w((11, 0)), (12, 0));
+        assert_eq!(b.word_end(0), 5);
+        assert_eq!(b.word_end(6), 10);
+    }
+
+    #[test]
+    fn test_target_chars() {
+        let b = Buffer::from_str("ae\u{301}b\u{1f1ec}\u{1f1f7}");
+        assert_eq!(target(&b, (0, 0), Motion::Right, 0), (1, 0));
+        assert_eq!(target(&b, (1, 0), Motion::Right, 1), (3, 0));
+        assert_eq!(target(&b, (0, 0), Motion::Right, 3), (4, 0));
+        assert_eq!(target(&b, (4, 0), Motion::Right, 9), (6, 0));
+        assert_eq!(target(&b, (4, 0), Motion::Left, 2), (1, 0));
+        assert_eq!(target(&b, (3, 0), Motion::Left, 9), (0, 0));
+    }
+
+    #[test]
+    fn test_target_lines() {
+        let b = Buffer::from_str("one\n  two\nthree");
+        assert_eq!(target(&b, (1, 0), Motion::LineEnd, 0), (2, 0));
+        assert_eq!(target(&b, (1, 0), Motion::LineEnd, 2), (4, 1));
+        assert_eq!(target(&b, (4, 1), Motion::FirstNonBlank, 0), (2, 1));
+        assert_eq!(target(&b, (0, 0), Motion::GotoLine, 0), (0, 2));
+        assert_eq!(target(&b, (0, 0), Motion::GotoLine, 2), (2, 1));
+        assert_eq!(target(&b, (0, 2), Motion::FirstLine, 0), (0, 0));
+        assert_eq!(target(&b, (0, 1), Motion::Down, 9), (0, 2));
+        assert_eq!(target(&b, (0, 0), Motion::Line, 2), (0, 1));
+        assert_eq!(target(&b, (0, 0), Motion::ParagraphForward, 0), (5, 2));
+        let b = Buffer::from_str("a\n\nb\nc\n\nd");
+        assert_eq!(target(&b, (0, 0), Motion::ParagraphForward, 2), (0, 4));
+        assert_eq!(target(&b, (0, 5), Motion::ParagraphBackward, 1), (0, 4));
+    }
+
+    #[test]
+    fn test_ordered() {
+        assert_eq!(ordered((5, 0), (1, 1)), ((5, 0), (1, 1)));
+        assert_eq!(ordered((1, 1), (5, 0)), ((5, 0), (1, 1)));
+        assert_eq!(ordered((3, 2), (1, 2)), ((1, 2), (3, 2)));
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use crate::editor::Direction;
+    use crate::window::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        w: 81,
+        h: 24,
+    };
+
+    #[test]
+    fn test_rects_single() {
+        let l = Layout::Window(0);
+        assert_eq!(l.rects(AREA), vec![(0, AREA)]);
+        assert_eq!(l.separators(AREA), vec![]);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut l = Layout::Window(0);
+        assert!(l.split(0, 1, Split::Horizontal));
+        assert!(l.split(1, 2, Split::Vertical));
+        assert!(!l.split(3, 4, Split::Vertical));
+        assert_eq!(
+            l.rects(AREA),
+            vec![
+                (
+                    0,
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        w: 81,
+                        h: 12
+                    }
+                ),
+                (
+                    1,
+                    Rect {
+                        x: 0,
+                        y: 12,
+                        w: 40,
+                        h: 12
+                    }
+                ),
+                (
+                    2,
+                    Rect {
+                        x: 41,
+                        y: 12,
+                        w: 40,
+                        h: 12
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            l.separators(AREA),
+            vec![Rect {
+                x: 40,
+                y: 12,
+                w: 1,
+                h: 12
+            }]
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut l = Layout::Window(0);
+        l.split(0, 1, Split::Horizontal);
+        l.split(1, 2, Split::Vertical);
+        assert!(l.remove(1));
+        assert_eq!(
+            l.rects(AREA),
+            vec![
+                (
+                    0,
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        w: 81,
+                        h: 12
+                    }
+                ),
+                (
+                    1,
+                    Rect {
+                        x: 0,
+                        y: 12,
+                        w: 81,
+                        h: 12
+                    }
+                ),
+            ]
+        );
+        assert!(!l.remove(5));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut l = Layout::Window(0);
+        assert!(!l.resize(0, 1, AREA));
+        l.split(0, 1, Split::Horizontal);
+        assert!(l.resize(1, 2, AREA));
+        assert_eq!(
+            l.rects(AREA)[1].1,
+            Rect {
+                x: 0,
+                y: 10,
+                w: 81,
+                h: 14
+            }
+        );
+        assert!(l.resize(0, 100, AREA));
+        assert_eq!(
+            l.rects(AREA)[1].1,
+            Rect {
+                x: 0,
+                y: 22,
+                w: 81,
+                h: 2
+            }
+        );
+        assert!(l.resize(1, 100, AREA));
+        assert_eq!(l.rects(AREA)[0].1.h, 2);
+    }
+
+    #[test]
+    fn test_min_width() {
+        let mut l = Layout::Window(0);
+        l.split(0, 1, Split::Vertical);
+        assert!(l.resize(0, -100, AREA));
+        let rects = l.rects(AREA);
+        assert_eq!(rects[0].1.w, MIN_WIDTH);
+        assert_eq!(rects[1].1.w, 80 - MIN_WIDTH);
+    }
+
+    #[test]
+    fn test_neighbour() {
+        let mut l = Layout::Window(0);
+        l.split(0, 1, Split::Horizontal);
+        l.split(1, 2, Split::Vertical);
+        let rects = l.rects(AREA);
+        assert_eq!(neighbour(&rects, 0, Direction::Down, (50, 5)), Some(2));
+        assert_eq!(neighbour(&rects, 0, Direction::Down, (5, 5)), Some(1));
+        assert_eq!(neighbour(&rects, 1, Direction::Right, (5, 15)), Some(2));
+        assert_eq!(neighbour(&rects, 2, Direction::Left, (45, 15)), Some(1));
+        assert_eq!(neighbour(&rects, 2, Direction::Up, (45, 15)), Some(0));
+        assert_eq!(neighbour(&rects, 0, Direction::Up, (5, 5)), None);
+    }
+}
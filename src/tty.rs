@@ -5,7 +5,7 @@ use std::io::{Stdout, Write};
 use std::time::Duration;
 
 use crossterm::{
-    event::{self, Event},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     terminal::{Clear, ClearType},
     QueueableCommand,
 };
@@ -27,7 +27,7 @@ impl Terminal {
         Ok(Terminal {
             wx: x as usize - 1,
             wy: y as usize - 1,
-            stdout: stdout,
+            stdout,
         })
     }
 
@@ -36,6 +36,7 @@ impl Terminal {
         self.stdout
             .queue(Clear(ClearType::All))?
             .queue(crossterm::event::EnableMouseCapture)?
+            .queue(crossterm::event::EnableBracketedPaste)?
             .flush()?;
         Ok(())
     }
@@ -44,6 +45,7 @@ impl Terminal {
         self.stdout
             .queue(crossterm::style::ResetColor)?
             .queue(crossterm::event::DisableMouseCapture)?
+            .queue(crossterm::event::DisableBracketedPaste)?
             .queue(crossterm::style::ResetColor)?
             .queue(Clear(ClearType::All))?
             .queue(crossterm::cursor::MoveTo(0, 0))?
@@ -53,18 +55,31 @@ impl Terminal {
     }
 
     pub fn get_event(&mut self) -> Result<Option<Event>> {
-        if event::poll(Duration::from_millis(1000))? {
-            match event::read()? {
-                Event::Resize(w, h) => {
-                    self.wx = w as usize - 1;
-                    self.wy = h as usize - 1;
-                    Ok(Some(Event::Resize(w, h)))
-                }
-                ev => Ok(Some(ev)),
+        if !event::poll(Duration::from_millis(1000))? {
+            return Ok(None);
+        }
+        match self.read_event()? {
+            // Only reported by terminals that tell presses from releases
+            Event::Key(k) if k.kind == KeyEventKind::Release => Ok(None),
+            ev => Ok(Some(ev)),
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Event> {
+        let mut ev = event::read()?;
+        match ev {
+            Event::Resize(w, h) => {
+                self.wx = w as usize - 1;
+                self.wy = h as usize - 1;
+            }
+            // Upper-case letters come with SHIFT, which the char already
+            // says; drop it so that they match bindings like "G"
+            Event::Key(ref mut k) if matches!(k.code, KeyCode::Char(_)) => {
+                k.modifiers.remove(KeyModifiers::SHIFT);
             }
-        } else {
-            Ok(None)
+            _ => {}
         }
+        Ok(ev)
     }
 }
 
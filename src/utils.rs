@@ -1,6 +1,10 @@
+use unicode_segmentation::UnicodeSegmentation;
+
 pub trait StringUtils {
     fn uslice(&self, start: usize, end: usize) -> String;
     fn ulen(&self) -> usize;
+    fn next_grapheme(&self, i: usize) -> usize;
+    fn prev_grapheme(&self, i: usize) -> usize;
 }
 
 impl StringUtils for str {
@@ -10,4 +14,27 @@ impl StringUtils for str {
     fn ulen(&self) -> usize {
         self.chars().count()
     }
+    /// The char index of the grapheme cluster boundary after char index i.
+    fn next_grapheme(&self, i: usize) -> usize {
+        let mut at = 0;
+        for g in self.graphemes(true) {
+            at += g.chars().count();
+            if at > i {
+                return at;
+            }
+        }
+        at
+    }
+    /// The char index of the grapheme cluster boundary before char index i.
+    fn prev_grapheme(&self, i: usize) -> usize {
+        let mut at = 0;
+        for g in self.graphemes(true) {
+            let next = at + g.chars().count();
+            if next >= i {
+                return at;
+            }
+            at = next;
+        }
+        at
+    }
 }
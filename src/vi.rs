@@ -0,0 +1,464 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::buffer::Buffer;
+use crate::editor::Command;
+use crate::keys;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    // w, b and e, or with true W, B and E, whose words are all that is
+    // between blanks
+    WordForward(bool),
+    WordBackward(bool),
+    WordEnd(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    ParagraphForward,
+    ParagraphBackward,
+    // G: the line given by the count, or the last line
+    GotoLine,
+    // gg: the line given by the count, or the first line
+    FirstLine,
+    // The current line, as in dd, cc and yy
+    Line,
+}
+
+impl Motion {
+    /// Whether an operator applied with this motion works on whole lines.
+    pub fn linewise(self) -> bool {
+        matches!(
+            self,
+            Motion::Up
+                | Motion::Down
+                | Motion::GotoLine
+                | Motion::FirstLine
+                | Motion::Line
+        )
+    }
+
+    /// Whether the char at the end of the motion is included when an
+    /// operator is applied.
+    pub fn inclusive(self) -> bool {
+        matches!(self, Motion::WordEnd(_) | Motion::LineEnd)
+    }
+}
+
+/// Where to start inserting text when entering insert mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsertAt {
+    // i, a
+    Cursor,
+    After,
+    // I, A
+    LineStart,
+    LineEnd,
+    // o, O
+    LineBelow,
+    LineAbove,
+}
+
+/// A complete vi command, ready to be executed by the editor. Counts are 0
+/// when none was given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Move(Motion, usize),
+    Operate(Operator, Motion, usize),
+    // Apply an operator to the visual selection
+    OperateSelection(Operator),
+    Insert(InsertAt),
+    Paste { before: bool, count: usize },
+    Mode(Mode),
+    // Repeat the last search, reversing its direction if asked to
+    SearchNext { reverse: bool },
+    Command(Command),
+}
+
+/// Result of feeding a key to the vi state machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Done(Action),
+    // The key starts or continues a command
+    Wait,
+    // Not a vi key; handled by the editor's keymap instead
+    Pass,
+    Invalid,
+}
+
+/// Vi modal editing state: the current mode, the register, and a command
+/// being typed in normal or visual mode. Insert mode keys go through the
+/// editor's keymap.
+#[derive(Debug, Clone)]
+pub struct Vi {
+    pub mode: Mode,
+    // Where the visual selection started
+    pub anchor: (usize, usize),
+    // Last text deleted or yanked, and whether it is made of whole lines
+    pub register: String,
+    pub linewise: bool,
+    // Direction of the last search started with / or ?
+    pub search_forward: bool,
+    // Keys of the command typed so far, for the status line
+    pub typed: String,
+    count: usize,
+    op: Option<(Operator, usize)>,
+    g: bool,
+}
+
+impl Default for Vi {
+    fn default() -> Vi {
+        Vi::new()
+    }
+}
+
+impl Vi {
+    pub fn new() -> Vi {
+        Vi {
+            mode: Mode::Normal,
+            anchor: (0, 0),
+            register: String::new(),
+            linewise: false,
+            search_forward: true,
+            typed: String::new(),
+            count: 0,
+            op: None,
+            g: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.typed.clear();
+        self.count = 0;
+        self.op = None;
+        self.g = false;
+    }
+
+    /// Handle a key in normal or visual mode.
+    pub fn key(&mut self, k: KeyEvent) -> Step {
+        let step = self.step(k);
+        match step {
+            Step::Wait => self.typed.push_str(&keys::display(k)),
+            _ => self.reset(),
+        }
+        step
+    }
+
+    fn step(&mut self, k: KeyEvent) -> Step {
+        let visual = self.mode == Mode::Visual;
+        let c = match (k.code, k.modifiers) {
+            (KeyCode::Char(c), m) if m.is_empty() => c,
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                return Step::Done(Action::Command(Command::Redo))
+            }
+            (KeyCode::Esc, _) if visual => {
+                return Step::Done(Action::Mode(Mode::Normal))
+            }
+            (KeyCode::Esc, _) => return Step::Invalid,
+            (KeyCode::Left | KeyCode::Backspace, _) => 'h',
+            (KeyCode::Right, _) => 'l',
+            (KeyCode::Up, _) => 'k',
+            (KeyCode::Down | KeyCode::Enter, _) => 'j',
+            (KeyCode::Home, _) => '0',
+            (KeyCode::End, _) => '$',
+            _ if self.typed.is_empty() => return Step::Pass,
+            _ => return Step::Invalid,
+        };
+
+        if self.g {
+            return match c {
+                'g' => self.motion(Motion::FirstLine),
+                _ => Step::Invalid,
+            };
+        }
+        if let Some(d) = c.to_digit(10) {
+            if d != 0 || self.count > 0 {
+                self.count = self.count * 10 + d as usize;
+                return Step::Wait;
+            }
+        }
+        let motion = match c {
+            'h' => Some(Motion::Left),
+            'l' | ' ' => Some(Motion::Right),
+            'k' => Some(Motion::Up),
+            'j' => Some(Motion::Down),
+            'w' => Some(Motion::WordForward(false)),
+            'b' => Some(Motion::WordBackward(false)),
+            'e' => Some(Motion::WordEnd(false)),
+            'W' => Some(Motion::WordForward(true)),
+            'B' => Some(Motion::WordBackward(true)),
+            'E' => Some(Motion::WordEnd(true)),
+            '0' => Some(Motion::LineStart),
+            '^' => Some(Motion::FirstNonBlank),
+            '$' => Some(Motion::LineEnd),
+            '}' => Some(Motion::ParagraphForward),
+            '{' => Some(Motion::ParagraphBackward),
+            'G' => Some(Motion::GotoLine),
+            _ => None,
+        };
+        if let Some(m) = motion {
+            return self.motion(m);
+        }
+        if c == 'g' {
+            self.g = true;
+            return Step::Wait;
+        }
+        let op = match c {
+            'd' => Some(Operator::Delete),
+            'c' => Some(Operator::Change),
+            'y' => Some(Operator::Yank),
+            _ => None,
+        };
+        if let Some(op) = op {
+            if visual {
+                return Step::Done(Action::OperateSelection(op));
+            }
+            return match self.op {
+                Some((op0, count0)) if op0 == op => {
+                    Step::Done(Action::Operate(
+                        op,
+                        Motion::Line,
+                        total(count0, self.count),
+                    ))
+                }
+                Some(_) => Step::Invalid,
+                None => {
+                    self.op = Some((op, self.count));
+                    self.count = 0;
+                    Step::Wait
+                }
+            };
+        }
+        if self.op.is_some() {
+            return Step::Invalid;
+        }
+
+        let count = self.count;
+        let operate = |op, m| Step::Done(Action::Operate(op, m, count));
+        if visual {
+            return match c {
+                'x' => Step::Done(Action::OperateSelection(Operator::Delete)),
+                's' => Step::Done(Action::OperateSelection(Operator::Change)),
+                'v' => Step::Done(Action::Mode(Mode::Normal)),
+                _ => Step::Invalid,
+            };
+        }
+        match c {
+            'i' => Step::Done(Action::Insert(InsertAt::Cursor)),
+            'a' => Step::Done(Action::Insert(InsertAt::After)),
+            'I' => Step::Done(Action::Insert(InsertAt::LineStart)),
+            'A' => Step::Done(Action::Insert(InsertAt::LineEnd)),
+            'o' => Step::Done(Action::Insert(InsertAt::LineBelow)),
+            'O' => Step::Done(Action::Insert(InsertAt::LineAbove)),
+            'x' => operate(Operator::Delete, Motion::Right),
+            'X' => operate(Operator::Delete, Motion::Left),
+            'D' => operate(Operator::Delete, Motion::LineEnd),
+            'C' => operate(Operator::Change, Motion::LineEnd),
+            's' => operate(Operator::Change, Motion::Right),
+            'S' => operate(Operator::Change, Motion::Line),
+            'Y' => operate(Operator::Yank, Motion::Line),
+            'p' => Step::Done(Action::Paste {
+                before: false,
+                count,
+            }),
+            'P' => Step::Done(Action::Paste {
+                before: true,
+                count,
+            }),
+            'v' => Step::Done(Action::Mode(Mode::Visual)),
+            'u' => Step::Done(Action::Command(Command::Undo)),
+            ':' => Step::Done(Action::Command(Command::Execute)),
+            '/' => {
+                self.search_forward = true;
+                Step::Done(Action::Command(Command::SearchForward))
+            }
+            '?' => {
+                self.search_forward = false;
+                Step::Done(Action::Command(Command::SearchBackward))
+            }
+            'n' => Step::Done(Action::SearchNext { reverse: false }),
+            'N' => Step::Done(Action::SearchNext { reverse: true }),
+            _ => Step::Invalid,
+        }
+    }
+
+    fn motion(&mut self, m: Motion) -> Step {
+        Step::Done(match self.op {
+            Some((op, count0)) => {
+                Action::Operate(op, m, total(count0, self.count))
+            }
+            None => Action::Move(m, self.count),
+        })
+    }
+}
+
+/// Combine the counts given before the operator and before the motion, as
+/// in 2d3w.
+fn total(a: usize, b: usize) -> usize {
+    if a == 0 && b == 0 {
+        0
+    } else {
+        a.max(1) * b.max(1)
+    }
+}
+
+/// A word motion: where it takes the cursor from a position, for small
+/// words or, with big set, for WORDs.
+type WordMotion = fn(&Buffer, (usize, usize), bool) -> (usize, usize);
+
+/// Start of the next word after (x, y). An empty line counts as a word.
+fn word_forward(
+    buf: &Buffer,
+    (x, y): (usize, usize),
+    big: bool,
+) -> (usize, usize) {
+    if let Some(&(start, _)) = buf.words(y, big).iter().find(|w| w.0 > x) {
+        return (start, y);
+    }
+    for y in y + 1..buf.len_lines() {
+        match buf.words(y, big).first() {
+            Some(&(start, _)) => return (start, y),
+            None if buf.line_len(y) == 0 => return (0, y),
+            None => (),
+        }
+    }
+    let last = buf.len_lines() - 1;
+    (buf.line_len(last), last)
+}
+
+/// Start of the word before (x, y).
+fn word_backward(
+    buf: &Buffer,
+    (x, y): (usize, usize),
+    big: bool,
+) -> (usize, usize) {
+    let words = buf.words(y, big);
+    if let Some(&(start, _)) = words.iter().rev().find(|w| w.0 < x) {
+        return (start, y);
+    }
+    for y in (0..y).rev() {
+        match buf.words(y, big).last() {
+            Some(&(start, _)) => return (start, y),
+            None if buf.line_len(y) == 0 => return (0, y),
+            None => (),
+        }
+    }
+    (0, 0)
+}
+
+/// Last grapheme of the word after (x, y), or (x, y) if there is none.
+fn word_end(
+    buf: &Buffer,
+    (x, y): (usize, usize),
+    big: bool,
+) -> (usize, usize) {
+    let last = |y, (_, end): (usize, usize)| (buf.prev_grapheme(end, y), y);
+    let words = buf.words(y, big);
+    if let Some(&w) = words.iter().find(|&&w| last(y, w).0 > x) {
+        return last(y, w);
+    }
+    for y in y + 1..buf.len_lines() {
+        if let Some(&w) = buf.words(y, big).first() {
+            return last(y, w);
+        }
+    }
+    (x, y)
+}
+
+/// The two positions in buffer order.
+pub fn ordered(
+    a: (usize, usize),
+    b: (usize, usize),
+) -> ((usize, usize), (usize, usize)) {
+    if (a.1, a.0) <= (b.1, b.0) {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Where motion m, repeated count times, takes the cursor from (x, y).
+pub fn target(
+    buf: &Buffer,
+    (x, y): (usize, usize),
+    m: Motion,
+    count: usize,
+) -> (usize, usize) {
+    let n = count.max(1);
+    let last = buf.len_lines() - 1;
+    let repeat =
+        |f: WordMotion, big| (0..n).fold((x, y), |pos, _| f(buf, pos, big));
+    match m {
+        Motion::Left => {
+            let x = (0..n).fold(x, |x, _| buf.prev_grapheme(x, y));
+            (x, y)
+        }
+        Motion::Right => {
+            let x = (0..n).fold(x, |x, _| buf.next_grapheme(x, y));
+            (x, y)
+        }
+        Motion::Up => (x, y.saturating_sub(n)),
+        Motion::Down => (x, (y + n).min(last)),
+        Motion::WordForward(big) => repeat(word_forward, big),
+        Motion::WordBackward(big) => repeat(word_backward, big),
+        Motion::WordEnd(big) => repeat(word_end, big),
+        Motion::LineStart => (0, y),
+        Motion::FirstNonBlank => (buf.first_non_blank(y), y),
+        Motion::LineEnd => {
+            let y = (y + n - 1).min(last);
+            (buf.prev_grapheme(buf.line_len(y), y), y)
+        }
+        Motion::GotoLine | Motion::FirstLine => {
+            let y = match (count, m) {
+                (0, Motion::GotoLine) => last,
+                (0, _) => 0,
+                _ => (count - 1).min(last),
+            };
+            (buf.first_non_blank(y), y)
+        }
+        Motion::ParagraphForward | Motion::ParagraphBackward => {
+            let mut y = y;
+            for _ in 0..n {
+                y = match m {
+                    Motion::ParagraphForward => buf.paragraph_end(y),
+                    _ => buf.paragraph_start(y),
+                };
+            }
+            if y == last && m == Motion::ParagraphForward {
+                (buf.line_len(y), y)
+            } else {
+                (0, y)
+            }
+        }
+        Motion::Line => (0, (y + n - 1).min(last)),
+    }
+}
@@ -0,0 +1,302 @@
+use crate::editor::Direction;
+
+// The smallest a window is made by splitting and resizing: room for a line
+// number gutter and some text, and for a line of text and the status line
+pub const MIN_WIDTH: usize = 10;
+pub const MIN_HEIGHT: usize = 2;
+
+/// A rectangular area of the screen, in cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+/// A view into a buffer. Each window has its own cursor and scroll offset,
+/// so the same buffer can be shown in several windows at once.
+#[derive(Debug, Clone)]
+pub struct Window {
+    // Index into Editor::buffers
+    pub buf: usize,
+    // Cursor position (in file)
+    pub cx: usize,
+    pub cy: usize,
+    // Offset (window scrolling)
+    pub ox: usize,
+    pub oy: usize,
+    // Position set with SetMark, if any
+    pub mark: Option<(usize, usize)>,
+    // The mark was set by moving with shift held, and goes away with the
+    // next plain movement
+    pub selecting: bool,
+}
+
+impl Window {
+    pub fn new(buf: usize) -> Window {
+        Window {
+            buf,
+            cx: 0,
+            cy: 0,
+            ox: 0,
+            oy: 0,
+            mark: None,
+            selecting: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Split {
+    // One window above the other
+    Horizontal,
+    // Windows side by side
+    Vertical,
+}
+
+/// How windows are arranged on the screen. Leaves are indices into
+/// Editor::windows; each split divides its area between two children.
+#[derive(Debug, Clone)]
+pub enum Layout {
+    Window(usize),
+    Split {
+        split: Split,
+        // Share of the area given to the first child
+        ratio: f32,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    /// Divide area between two children, keeping both at least the
+    /// minimum size if the area is big enough. Vertical splits reserve a
+    /// column for the separator, which is returned as the third element.
+    fn divide(
+        split: Split,
+        ratio: f32,
+        area: Rect,
+    ) -> (Rect, Rect, Option<Rect>) {
+        match split {
+            Split::Horizontal => {
+                let h1 = (area.h as f32 * ratio).round() as usize;
+                let h1 = h1
+                    .min(area.h.saturating_sub(MIN_HEIGHT))
+                    .max(MIN_HEIGHT.min(area.h / 2));
+                (
+                    Rect { h: h1, ..area },
+                    Rect {
+                        y: area.y + h1,
+                        h: area.h - h1,
+                        ..area
+                    },
+                    None,
+                )
+            }
+            Split::Vertical => {
+                let avail = area.w.saturating_sub(1);
+                let w1 = (avail as f32 * ratio).round() as usize;
+                let w1 = w1
+                    .min(avail.saturating_sub(MIN_WIDTH))
+                    .max(MIN_WIDTH.min(avail / 2));
+                (
+                    Rect { w: w1, ..area },
+                    Rect {
+                        x: area.x + w1 + 1,
+                        w: avail - w1,
+                        ..area
+                    },
+                    Some(Rect {
+                        x: area.x + w1,
+                        w: 1,
+                        ..area
+                    }),
+                )
+            }
+        }
+    }
+
+    fn walk(
+        &self,
+        area: Rect,
+        windows: &mut Vec<(usize, Rect)>,
+        separators: &mut Vec<Rect>,
+    ) {
+        match self {
+            Layout::Window(w) => windows.push((*w, area)),
+            Layout::Split {
+                split,
+                ratio,
+                first,
+                second,
+            } => {
+                let (a, b, sep) = Layout::divide(*split, *ratio, area);
+                first.walk(a, windows, separators);
+                if let Some(sep) = sep {
+                    separators.push(sep);
+                }
+                second.walk(b, windows, separators);
+            }
+        }
+    }
+
+    /// Screen area of every window, in layout order.
+    pub fn rects(&self, area: Rect) -> Vec<(usize, Rect)> {
+        let mut windows = Vec::new();
+        self.walk(area, &mut windows, &mut Vec::new());
+        windows
+    }
+
+    /// Columns between windows placed side by side.
+    pub fn separators(&self, area: Rect) -> Vec<Rect> {
+        let mut separators = Vec::new();
+        self.walk(area, &mut Vec::new(), &mut separators);
+        separators
+    }
+
+    /// Replace window win with a split between win and new.
+    pub fn split(&mut self, win: usize, new: usize, split: Split) -> bool {
+        match self {
+            Layout::Window(w) if *w == win => {
+                *self = Layout::Split {
+                    split,
+                    ratio: 0.5,
+                    first: Box::new(Layout::Window(win)),
+                    second: Box::new(Layout::Window(new)),
+                };
+                true
+            }
+            Layout::Window(_) => false,
+            Layout::Split { first, second, .. } => {
+                first.split(win, new, split) || second.split(win, new, split)
+            }
+        }
+    }
+
+    /// Remove window win, giving its area to its sibling. Indices of the
+    /// windows after it are shifted down by one, to match removing it from
+    /// Editor::windows.
+    pub fn remove(&mut self, win: usize) -> bool {
+        let removed = self.remove_leaf(win);
+        if removed {
+            self.renumber(win);
+        }
+        removed
+    }
+
+    fn remove_leaf(&mut self, win: usize) -> bool {
+        if let Layout::Split { first, second, .. } = self {
+            if let Layout::Window(w) = **first {
+                if w == win {
+                    *self = (**second).clone();
+                    return true;
+                }
+            }
+            if let Layout::Window(w) = **second {
+                if w == win {
+                    *self = (**first).clone();
+                    return true;
+                }
+            }
+            return first.remove_leaf(win) || second.remove_leaf(win);
+        }
+        false
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Window(w) => {
+                if *w > removed {
+                    *w -= 1;
+                }
+            }
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+
+    /// Grow (or shrink, for negative delta) window win by delta cells, by
+    /// moving the edge of the innermost split that contains it.
+    pub fn resize(&mut self, win: usize, delta: isize, area: Rect) -> bool {
+        if let Layout::Split {
+            split,
+            ratio,
+            first,
+            second,
+        } = self
+        {
+            let (a, b, _) = Layout::divide(*split, *ratio, area);
+            if first.resize(win, delta, a) || second.resize(win, delta, b) {
+                return true;
+            }
+            let total = match split {
+                Split::Horizontal => area.h,
+                Split::Vertical => area.w.saturating_sub(1),
+            };
+            if total < 2 {
+                return false;
+            }
+            let step = delta as f32 / total as f32;
+            if first.contains(win) {
+                *ratio += step;
+            } else if second.contains(win) {
+                *ratio -= step;
+            } else {
+                return false;
+            }
+            *ratio = ratio.clamp(0.0, 1.0);
+            return true;
+        }
+        false
+    }
+
+    fn contains(&self, win: usize) -> bool {
+        match self {
+            Layout::Window(w) => *w == win,
+            Layout::Split { first, second, .. } => {
+                first.contains(win) || second.contains(win)
+            }
+        }
+    }
+}
+
+/// Find the window next to from in direction d, preferring the one that
+/// lines up with the screen position at.
+pub fn neighbour(
+    rects: &[(usize, Rect)],
+    from: usize,
+    d: Direction,
+    at: (usize, usize),
+) -> Option<usize> {
+    let r = rects.iter().find(|(w, _)| *w == from)?.1;
+    let candidates: Vec<&(usize, Rect)> = rects
+        .iter()
+        .filter(|(_, c)| match d {
+            Direction::Up => c.y + c.h == r.y && overlaps_x(c, &r),
+            Direction::Down => c.y == r.y + r.h && overlaps_x(c, &r),
+            Direction::Left => c.x + c.w + 1 == r.x && overlaps_y(c, &r),
+            Direction::Right => c.x == r.x + r.w + 1 && overlaps_y(c, &r),
+        })
+        .collect();
+    candidates
+        .iter()
+        .find(|(_, c)| match d {
+            Direction::Up | Direction::Down => c.x <= at.0 && at.0 < c.x + c.w,
+            Direction::Left | Direction::Right => {
+                c.y <= at.1 && at.1 < c.y + c.h
+            }
+        })
+        .or_else(|| candidates.first())
+        .map(|(w, _)| *w)
+}
+
+fn overlaps_x(a: &Rect, b: &Rect) -> bool {
+    a.x < b.x + b.w && b.x < a.x + a.w
+}
+
+fn overlaps_y(a: &Rect, b: &Rect) -> bool {
+    a.y < b.y + b.h && b.y < a.y + a.h
+}